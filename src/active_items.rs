@@ -0,0 +1,257 @@
+use crate::gameplay::{Combative, Team, TeamWrap};
+use crate::physics::{Bullet, PhysicsComponent};
+use crate::player::PlayerControls;
+use crate::prelude::*;
+use crate::{Event, EventQueue};
+use serde::Deserialize;
+
+const BOMB_RADIUS: f32 = 300.0;
+const SHIELD_TIME: f32 = 3.0;
+const TIME_SLOW_TIME: f32 = 4.0;
+// How fast enemies and their bullets move while time is slowed.
+const TIME_SLOW_FACTOR: f32 = 0.35;
+const TURRET_LIFETIME: f32 = 8.0;
+const TURRET_FIRE_COOLDOWN: f32 = 0.4;
+const TURRET_RANGE: f32 = 350.0;
+
+// Used with Space. The player holds at most one at a time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum ActiveItem {
+    Bomb,
+    Shield,
+    TimeSlow,
+    Turret,
+}
+
+impl ActiveItem {
+    pub fn all() -> [ActiveItem; 4] {
+        [
+            ActiveItem::Bomb,
+            ActiveItem::Shield,
+            ActiveItem::TimeSlow,
+            ActiveItem::Turret,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ActiveItem::Bomb => "Bomb",
+            ActiveItem::Shield => "Shield",
+            ActiveItem::TimeSlow => "Hourglass",
+            ActiveItem::Turret => "Turret",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ActiveItem::Bomb => "Clears nearby enemy bullets.",
+            ActiveItem::Shield => "Briefly invulnerable.",
+            ActiveItem::TimeSlow => "Slows enemies and their bullets.",
+            ActiveItem::Turret => "Deploys a turret that shoots enemies.",
+        }
+    }
+
+    pub fn colour(self) -> Color {
+        match self {
+            ActiveItem::Bomb => rgba!(255, 120, 40, 1.0),
+            ActiveItem::Shield => rgba!(80, 160, 255, 1.0),
+            ActiveItem::TimeSlow => rgba!(230, 210, 140, 1.0),
+            ActiveItem::Turret => rgba!(150, 150, 170, 1.0),
+        }
+    }
+
+    // Seconds between uses.
+    pub fn cooldown(self) -> f32 {
+        match self {
+            ActiveItem::Bomb => 12.0,
+            ActiveItem::Shield => 15.0,
+            ActiveItem::TimeSlow => 18.0,
+            ActiveItem::Turret => 20.0,
+        }
+    }
+
+    fn sound(self) -> SoundRequest {
+        match self {
+            ActiveItem::Bomb => SoundRequest::Bomb,
+            ActiveItem::Shield => SoundRequest::Shield,
+            ActiveItem::TimeSlow => SoundRequest::TimeSlow,
+            ActiveItem::Turret => SoundRequest::Turret,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TimeSlow {
+    pub until: Timer,
+}
+
+impl TimeSlow {
+    // Time step for something on `team`, only enemies are slowed.
+    pub fn dt(&self, sim_time: SimTime, team: Option<&TeamWrap>) -> f32 {
+        match team {
+            Some(TeamWrap { team: Team::Enemy }) if !self.until.expired(sim_time) => {
+                sim_time.dt * TIME_SLOW_FACTOR
+            }
+            _ => sim_time.dt,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Turret {
+    pub fire_cooldown: Timer,
+    pub expires: Timer,
+}
+
+impl Component for Turret {
+    type Storage = HashMapStorage<Self>;
+}
+
+// Carries out active items used in PlayerControlSystem.
+pub struct ActiveItemSystem;
+
+impl<'a> System<'a> for ActiveItemSystem {
+    type SystemData = (
+        Read<'a, EventQueue>,
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Bullet>,
+        ReadStorage<'a, TeamWrap>,
+        WriteStorage<'a, Combative>,
+        Write<'a, TimeSlow>,
+        Read<'a, SimTime>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+        Write<'a, SoundQueue>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            event_queue,
+            entities,
+            transforms,
+            bullets,
+            teams,
+            mut combatives,
+            mut time_slow,
+            sim_time,
+            lazy_update,
+            prefabs,
+            mut sound_queue,
+        ): Self::SystemData,
+    ) {
+        for event in event_queue.iter() {
+            if let Event::ActiveItemUsed(player, item) = event {
+                let player_position = transforms
+                    .get(*player)
+                    .expect("Players should have a position.")
+                    .position;
+                match item {
+                    ActiveItem::Bomb => {
+                        for (ent, transform, _, team) in
+                            (&entities, &transforms, &bullets, &teams).join()
+                        {
+                            if team.team == Team::Enemy
+                                && (transform.position - player_position).len2()
+                                    < BOMB_RADIUS * BOMB_RADIUS
+                            {
+                                entities.delete(ent).unwrap();
+                            }
+                        }
+                    }
+                    ActiveItem::Shield => {
+                        if let Some(c) = combatives.get_mut(*player) {
+                            c.invincibility_cooldown.set(*sim_time, SHIELD_TIME);
+                        }
+                    }
+                    ActiveItem::TimeSlow => {
+                        time_slow.until.set(*sim_time, TIME_SLOW_TIME);
+                    }
+                    ActiveItem::Turret => {
+                        prefabs
+                            .spawn("turret", lazy_update.create_entity(&entities))
+                            .with(Turret {
+                                expires: Timer::new_set(*sim_time, TURRET_LIFETIME),
+                                ..Default::default()
+                            })
+                            .with(Transform {
+                                position: player_position,
+                            })
+                            .build();
+                    }
+                }
+                sound_queue.enqueue(item.sound());
+            }
+        }
+    }
+}
+
+pub struct TurretSystem;
+
+impl<'a> System<'a> for TurretSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Turret>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, Combative>,
+        ReadStorage<'a, TeamWrap>,
+        ReadStorage<'a, PlayerControls>,
+        Read<'a, SimTime>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut turrets,
+            transforms,
+            combatives,
+            teams,
+            players,
+            sim_time,
+            lazy_update,
+            prefabs,
+        ): Self::SystemData,
+    ) {
+        for (ent, turret, transform) in (&entities, &mut turrets, &transforms).join() {
+            if turret.expires.expired(*sim_time) {
+                entities.delete(ent).unwrap();
+                continue;
+            }
+            if !turret.fire_cooldown.expired(*sim_time) {
+                continue;
+            }
+            let target = (&transforms, &combatives, &teams, !&players)
+                .join()
+                .filter(|(_, _, team, _)| team.team == Team::Enemy)
+                .map(|(t, _, _, _)| t.position)
+                .filter(|p| (*p - transform.position).len2() < TURRET_RANGE * TURRET_RANGE)
+                .min_by(|a, b| {
+                    (*a - transform.position)
+                        .len2()
+                        .partial_cmp(&(*b - transform.position).len2())
+                        .unwrap()
+                });
+            if let Some(target) = target {
+                let bullet_speed = 400.0;
+                let velocity = (target - transform.position).with_len(bullet_speed);
+                prefabs
+                    .spawn("bullet", lazy_update.create_entity(&entities))
+                    .with(Transform {
+                        position: transform.position + velocity.with_len(15.0),
+                    })
+                    .with(PhysicsComponent {
+                        velocity,
+                        max_speed: bullet_speed,
+                        ..Default::default()
+                    })
+                    .with(TeamWrap { team: Team::Player })
+                    .build();
+                turret.fire_cooldown.set(*sim_time, TURRET_FIRE_COOLDOWN);
+            }
+        }
+    }
+}
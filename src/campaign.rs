@@ -0,0 +1,98 @@
+use crate::enemy_ai::BossAttack;
+use crate::level_generation::LevelStyle;
+use crate::world_generation::{ENCOUNTERS_PER_OASIS, MAX_SITES};
+use rand::Rng;
+use serde::Deserialize;
+
+// Campaigns are defined in campaign.ron. Progress through one is the index of the current tier.
+const CAMPAIGNS_SOURCE: (&str, &str) = ("campaign.ron", include_str!("../campaign.ron"));
+
+pub const STANDARD: &str = "Standard";
+pub const QUICK_RUN: &str = "Quick Run";
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossDefinition {
+    pub health: i32,
+    pub attacks: Vec<BossAttack>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Tier {
+    pub oases: usize,
+    #[serde(default)]
+    pub oasis_distance: (f32, f32),
+    pub range: f32,
+    pub dungeons: usize,
+    pub difficulty: i32,
+    pub floors: i32,
+    styles: Vec<LevelStyle>,
+    pub boss: BossDefinition,
+}
+
+impl Tier {
+    pub fn random_style(&self, rng: &mut impl Rng) -> LevelStyle {
+        self.styles[rng.gen_range(0, self.styles.len())]
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub tiers: Vec<Tier>,
+}
+
+impl Campaign {
+    pub fn load(name: &str) -> Campaign {
+        let (file, source) = CAMPAIGNS_SOURCE;
+        let campaigns: Vec<Campaign> = ron::de::from_str(source)
+            .unwrap_or_else(|e| panic!("Bad campaign definitions {}:{}", file, e));
+        for campaign in campaigns.iter() {
+            if campaign.tiers.is_empty() || campaign.tiers[0].oases == 0 {
+                panic!(
+                    "Bad campaign definitions {} ({}): the first tier needs an oasis",
+                    file, campaign.name
+                );
+            }
+            if campaign.tiers.iter().any(|t| t.styles.is_empty()) {
+                panic!(
+                    "Bad campaign definitions {} ({}): every tier needs a level style",
+                    file, campaign.name
+                );
+            }
+            if campaign.tiers.iter().any(|t| t.boss.attacks.is_empty()) {
+                panic!(
+                    "Bad campaign definitions {} ({}): every boss needs an attack",
+                    file, campaign.name
+                );
+            }
+            if campaign.sites_needed() > MAX_SITES {
+                panic!(
+                    "Bad campaign definitions {} ({}): needs {} map sites, at most {} fit",
+                    file,
+                    campaign.name,
+                    campaign.sites_needed(),
+                    MAX_SITES
+                );
+            }
+        }
+        campaigns
+            .into_iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("No campaign called '{}' in {}", name, file))
+    }
+
+    // Every oasis but the starting one, each dungeon including the bosses, and the encounters.
+    fn sites_needed(&self) -> usize {
+        let oases: usize = self.tiers.iter().map(|t| t.oases).sum();
+        let dungeons: usize = self.tiers.iter().map(|t| t.oases * t.dungeons + 1).sum();
+        oases - 1 + dungeons + oases * ENCOUNTERS_PER_OASIS
+    }
+
+    pub fn tier(&self, index: i32) -> &Tier {
+        &self.tiers[(index.max(0) as usize).min(self.tiers.len() - 1)]
+    }
+
+    pub fn is_final_tier(&self, index: i32) -> bool {
+        index as usize + 1 >= self.tiers.len()
+    }
+}
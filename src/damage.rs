@@ -0,0 +1,73 @@
+use crate::prelude::*;
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum DamageType {
+    Kinetic,
+    Fire,
+    Arcane,
+}
+
+// How much of each type of damage something deals.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Damage {
+    pub kinetic: i32,
+    pub fire: i32,
+    pub arcane: i32,
+}
+
+impl Damage {
+    pub fn of(damage_type: DamageType, amount: i32) -> Damage {
+        let mut damage = Damage::default();
+        damage.add(damage_type, amount);
+        damage
+    }
+
+    pub fn kinetic(amount: i32) -> Damage {
+        Damage::of(DamageType::Kinetic, amount)
+    }
+
+    pub fn add(&mut self, damage_type: DamageType, amount: i32) {
+        match damage_type {
+            DamageType::Kinetic => self.kinetic += amount,
+            DamageType::Fire => self.fire += amount,
+            DamageType::Arcane => self.arcane += amount,
+        }
+    }
+
+    // Total damage dealt to something with these resistances (or none).
+    pub fn against(self, resistances: Option<&Resistances>) -> i32 {
+        let resistances = match resistances {
+            Some(r) => *r,
+            None => Resistances::default(),
+        };
+        let total = self.kinetic as f32 * resistances.multiplier(DamageType::Kinetic)
+            + self.fire as f32 * resistances.multiplier(DamageType::Fire)
+            + self.arcane as f32 * resistances.multiplier(DamageType::Arcane);
+        total.round().max(0.0) as i32
+    }
+}
+
+// 0 takes normal damage, 1 is immune, negative values are weaknesses (-0.5 takes 150%).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Resistances {
+    pub kinetic: f32,
+    pub fire: f32,
+    pub arcane: f32,
+}
+
+impl Resistances {
+    fn multiplier(self, damage_type: DamageType) -> f32 {
+        let resistance = match damage_type {
+            DamageType::Kinetic => self.kinetic,
+            DamageType::Fire => self.fire,
+            DamageType::Arcane => self.arcane,
+        };
+        1.0 - resistance
+    }
+}
+
+impl Component for Resistances {
+    type Storage = HashMapStorage<Self>;
+}
@@ -0,0 +1,331 @@
+use crate::campaign::Campaign;
+use crate::gameplay::{apply_upgrade, Announcement, Combative};
+use crate::items::{Item, ItemRegistry};
+use crate::level_generation::{GenerationMetrics, LevelStyle};
+use crate::physics::{PhysicsComponent, TileMap};
+use crate::player::PlayerControls;
+use crate::prelude::*;
+use crate::render::encounter_rows;
+use crate::world_map::{self, enter_dungeon, Caravan, CurrentDungeon, Dungeon, Reward};
+use crate::{Camera, Input, PlayerProgression, ScreenSize, UIState};
+
+// Days lost sheltering from a sandstorm, and water dropped running from an ambush.
+const SHELTER_DAYS: i32 = 2;
+const FLEE_WATER: i32 = 3;
+const STORM_DAMAGE: i32 = 1;
+const AMBUSH_GOLD: i32 = 10;
+// Dungeons this close to a sandstorm get a new style.
+const STORM_RADIUS: f32 = 150.0;
+
+// Things that happen out in the desert between dungeons.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum EncounterKind {
+    // Reshapes the dungeons nearby.
+    Sandstorm,
+    Merchant(Item),
+    Ambush,
+    // Trades max health for an item.
+    Shrine(Item),
+}
+
+impl EncounterKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EncounterKind::Sandstorm => "Sandstorm",
+            EncounterKind::Merchant(_) => "Wandering Merchant",
+            EncounterKind::Ambush => "Ambush",
+            EncounterKind::Shrine(_) => "Shrine",
+        }
+    }
+
+    pub fn colour(self) -> Color {
+        match self {
+            EncounterKind::Sandstorm => rgba!(210, 180, 120, 1.0),
+            EncounterKind::Merchant(_) => Color::YELLOW,
+            EncounterKind::Ambush => Color::PURPLE,
+            EncounterKind::Shrine(_) => Color::CYAN,
+        }
+    }
+
+    pub fn description(self, item_registry: &ItemRegistry) -> String {
+        match self {
+            EncounterKind::Sandstorm => {
+                "A sandstorm sweeps over the dunes.\nThe dungeons nearby won't be the same after it."
+                    .to_string()
+            }
+            EncounterKind::Merchant(item) => format!(
+                "A wandering merchant offers you {}.",
+                item_registry.get(item).name
+            ),
+            EncounterKind::Ambush => "Bandits ambush your caravan!".to_string(),
+            EncounterKind::Shrine(item) => format!(
+                "An old shrine holds {}.\nIt asks for some of your vitality in return.",
+                item_registry.get(item).name
+            ),
+        }
+    }
+
+    pub fn options(self) -> Vec<EncounterOption> {
+        match self {
+            EncounterKind::Sandstorm => vec![EncounterOption::Shelter, EncounterOption::BraveStorm],
+            EncounterKind::Merchant(item) => {
+                vec![EncounterOption::Buy(item), EncounterOption::Leave]
+            }
+            EncounterKind::Ambush => vec![EncounterOption::Fight, EncounterOption::Flee],
+            EncounterKind::Shrine(item) => {
+                vec![EncounterOption::Offer(item), EncounterOption::Leave]
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum EncounterOption {
+    Shelter,
+    BraveStorm,
+    Buy(Item),
+    Fight,
+    Flee,
+    Offer(Item),
+    Leave,
+}
+
+impl EncounterOption {
+    // Merchants sell at the shop's base price, without the markup that comes with progress.
+    pub fn price(self, item_registry: &ItemRegistry) -> i32 {
+        match self {
+            EncounterOption::Buy(item) => item_registry.get(item).rarity.price(),
+            _ => 0,
+        }
+    }
+
+    pub fn label(self, item_registry: &ItemRegistry) -> (String, String) {
+        match self {
+            EncounterOption::Shelter => (
+                "Take shelter".to_string(),
+                format!("Wait it out, losing {} days of water.", SHELTER_DAYS),
+            ),
+            EncounterOption::BraveStorm => (
+                "Press on".to_string(),
+                format!("The sand stings. Lose {} health.", STORM_DAMAGE),
+            ),
+            EncounterOption::Buy(item) => {
+                let item_data = item_registry.get(item);
+                (
+                    format!("Buy {} ({}g)", item_data.name, self.price(item_registry)),
+                    item_data.description.clone(),
+                )
+            }
+            EncounterOption::Fight => (
+                "Fight".to_string(),
+                "Drive them off and take their gold.".to_string(),
+            ),
+            EncounterOption::Flee => (
+                "Flee".to_string(),
+                format!("Abandon {} days of water to get away.", FLEE_WATER),
+            ),
+            EncounterOption::Offer(item) => {
+                let item_data = item_registry.get(item);
+                (
+                    format!("Give 1 max health for {}", item_data.name),
+                    item_data.description.clone(),
+                )
+            }
+            EncounterOption::Leave => ("Leave".to_string(), "Move on.".to_string()),
+        }
+    }
+}
+
+pub struct Encounter {
+    pub position: Vector,
+    pub kind: EncounterKind,
+    pub resolved: bool,
+}
+
+impl Component for Encounter {
+    type Storage = HashMapStorage<Self>;
+}
+
+// The encounter being shown on the encounter screen.
+#[derive(Default)]
+pub struct CurrentEncounter {
+    pub entity: Option<Entity>,
+}
+
+pub fn random_encounter(item_registry: &ItemRegistry, rng: &mut impl Rng) -> EncounterKind {
+    match rng.gen_range(0, 4) {
+        0 => EncounterKind::Sandstorm,
+        1 => EncounterKind::Merchant(item_registry.draw(false, 1, rng)[0]),
+        2 => EncounterKind::Ambush,
+        _ => EncounterKind::Shrine(item_registry.draw(false, 1, rng)[0]),
+    }
+}
+
+pub struct EncounterSystem;
+
+impl<'a> System<'a> for EncounterSystem {
+    type SystemData = (
+        Read<'a, Input>,
+        Write<'a, UIState>,
+        Read<'a, CurrentEncounter>,
+        WriteStorage<'a, Encounter>,
+        WriteStorage<'a, Dungeon>,
+        Write<'a, PlayerProgression>,
+        Write<'a, Caravan>,
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, PhysicsComponent>,
+        Read<'a, ItemRegistry>,
+        Write<'a, Announcement>,
+        Write<'a, GenerationMetrics>,
+        Write<'a, TileMap>,
+        Write<'a, CurrentDungeon>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Camera>,
+        Read<'a, ScreenSize>,
+        Read<'a, Campaign>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            input,
+            mut ui_state,
+            current_encounter,
+            mut encounters,
+            mut dungeons,
+            mut progression,
+            mut caravan,
+            mut players,
+            mut combatives,
+            mut physics,
+            item_registry,
+            mut announcement,
+            mut generation_metrics,
+            mut tile_map,
+            mut current_dungeon,
+            entities,
+            lazy_update,
+            prefabs,
+            mut transforms,
+            mut cameras,
+            screen_size,
+            campaign,
+        ): Self::SystemData,
+    ) {
+        if !input.clicked {
+            return;
+        }
+        let encounter = current_encounter
+            .entity
+            .and_then(|e| encounters.get_mut(e))
+            .expect("The current encounter should be valid on the encounter screen.");
+        let options = encounter.kind.options();
+        let rows = encounter_rows(options.len());
+        let option = match rows
+            .iter()
+            .zip(options.iter())
+            .find(|(row, _)| row.contains(input.raw_mouse_pos))
+        {
+            Some((_, option)) => *option,
+            None => return,
+        };
+        match option {
+            EncounterOption::Shelter | EncounterOption::BraveStorm => {
+                if option == EncounterOption::Shelter {
+                    caravan.water = (caravan.water - SHELTER_DAYS).max(0);
+                    caravan.day += SHELTER_DAYS;
+                } else {
+                    world_map::wear_down(STORM_DAMAGE, &players, &mut combatives);
+                }
+                let mut rng = rand::thread_rng();
+                for d in (&mut dungeons).join() {
+                    if !d.completed
+                        && d.style != LevelStyle::Arena
+                        && (d.position - encounter.position).len() < STORM_RADIUS
+                    {
+                        d.style = campaign.tier(d.tier).random_style(&mut rng);
+                    }
+                }
+                announcement.text = Some("The sandstorm has reshaped the dungeons nearby.".into());
+            }
+            EncounterOption::Buy(item) => {
+                let price = option.price(&item_registry);
+                if progression.currency < price {
+                    return;
+                }
+                progression.currency -= price;
+                apply_upgrade(
+                    item,
+                    &item_registry,
+                    &mut players,
+                    &mut combatives,
+                    &mut physics,
+                    &mut announcement,
+                );
+            }
+            EncounterOption::Offer(item) => {
+                // The shrine won't take someone's last hit point.
+                if (&players, &combatives).join().any(|(_, c)| c.hp() <= 1) {
+                    return;
+                }
+                for p in (&mut players).join() {
+                    p.base_stats.max_hp -= 1;
+                }
+                apply_upgrade(
+                    item,
+                    &item_registry,
+                    &mut players,
+                    &mut combatives,
+                    &mut physics,
+                    &mut announcement,
+                );
+            }
+            EncounterOption::Flee => {
+                caravan.water = (caravan.water - FLEE_WATER).max(0);
+            }
+            EncounterOption::Fight => {
+                // The ambush is fought like a one floor dungeon, left on the map where it happened.
+                let difficulty = campaign.tier(progression.tier).difficulty;
+                let mut ambush = Dungeon {
+                    position: encounter.position,
+                    reward: Reward::Currency(AMBUSH_GOLD * difficulty),
+                    completed: false,
+                    style: LevelStyle::Arena,
+                    difficulty,
+                    floors: 1,
+                    current_floor: 1,
+                    tier: progression.tier,
+                    scouted: true,
+                };
+                let e = entities.create();
+                enter_dungeon(
+                    e,
+                    &mut ambush,
+                    &mut generation_metrics,
+                    &mut tile_map,
+                    &mut current_dungeon,
+                    &entities,
+                    &lazy_update,
+                    &prefabs,
+                    &players,
+                    &mut transforms,
+                    &mut cameras,
+                    &screen_size,
+                );
+                dungeons
+                    .insert(e, ambush)
+                    .expect("The ambush was only just created.");
+                encounter.resolved = true;
+                *ui_state = UIState::Playing;
+                return;
+            }
+            EncounterOption::Leave => {}
+        }
+        encounter.resolved = true;
+        *ui_state = UIState::WorldMap;
+    }
+}
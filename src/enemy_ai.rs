@@ -1,8 +1,11 @@
-use crate::gameplay::{Asleep, PenetratingBullet, Team, TeamWrap};
-use crate::physics::PhysicsComponent;
+use crate::damage::Damage;
+use crate::gameplay::{drop_loot, Asleep, Loot, PenetratingBullet, Team, TeamWrap};
+use crate::physics::{Bullet, PhysicsComponent};
 use crate::player::PlayerControls;
 use crate::prelude::*;
+use crate::status_effects::{StatusEffects, StatusKind};
 use crate::{Event, EventQueue};
+use serde::Deserialize;
 
 const TARGET_DISTANCE: f32 = 100.0;
 const CHODE_ACCELERATION: f32 = 300.0;
@@ -49,11 +52,28 @@ impl<'a> System<'a> for ChodeDeath {
         ReadStorage<'a, ShotgunnerAI>,
         ReadStorage<'a, SpinnerAI>,
         Write<'a, SoundQueue>,
+        ReadStorage<'a, Loot>,
+        ReadStorage<'a, Transform>,
+        Read<'a, SimTime>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
     );
 
     fn run(
         &mut self,
-        (entities, event_queue, chode_ais, shotgunner_ais, spinner_ais, mut sound_queue): Self::SystemData,
+        (
+            entities,
+            event_queue,
+            chode_ais,
+            shotgunner_ais,
+            spinner_ais,
+            mut sound_queue,
+            loots,
+            transforms,
+            sim_time,
+            lazy_update,
+            prefabs,
+        ): Self::SystemData,
     ) {
         for event in event_queue.iter() {
             if let Event::EntityKilled(ent) = event {
@@ -61,6 +81,16 @@ impl<'a> System<'a> for ChodeDeath {
                     || shotgunner_ais.get(*ent).is_some()
                     || spinner_ais.get(*ent).is_some()
                 {
+                    if let (Some(loot), Some(transform)) = (loots.get(*ent), transforms.get(*ent)) {
+                        drop_loot(
+                            loot,
+                            transform.position,
+                            *sim_time,
+                            &entities,
+                            &lazy_update,
+                            &prefabs,
+                        );
+                    }
                     entities.delete(*ent).unwrap();
                     sound_queue.enqueue(SoundRequest::EnemyDeath);
                 }
@@ -81,7 +111,9 @@ impl<'a> System<'a> for RunChodeAI {
         Read<'a, SimTime>,
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Write<'a, SoundQueue>,
+        ReadStorage<'a, StatusEffects>,
     );
 
     fn run(
@@ -95,7 +127,9 @@ impl<'a> System<'a> for RunChodeAI {
             sim_time,
             entities,
             lazy_update,
+            prefabs,
             mut sound_queue,
+            statuses,
         ): Self::SystemData,
     ) {
         let mut player_pos = Vector::new(0.0, 0.0);
@@ -103,9 +137,18 @@ impl<'a> System<'a> for RunChodeAI {
             player_pos = player_transform.position;
         }
 
-        for (chode, transform, physics, _) in
-            (&mut chode_ais, &transforms, &mut physics, !&asleeps).join()
+        for (chode, transform, physics, _, status) in (
+            &mut chode_ais,
+            &transforms,
+            &mut physics,
+            !&asleeps,
+            statuses.maybe(),
+        )
+            .join()
         {
+            if status.map_or(false, |s| s.immobilised()) {
+                continue;
+            }
             let target_point =
                 player_pos + (transform.position - player_pos).with_len(TARGET_DISTANCE);
             let dir = target_point - transform.position;
@@ -121,9 +164,8 @@ impl<'a> System<'a> for RunChodeAI {
                 let bullet_speed = 400.0;
                 let velocity = (player_pos - transform.position).with_len(bullet_speed);
                 let position = transform.position + velocity.with_len(30.0);
-                lazy_update
-                    .create_entity(&entities)
-                    .with_bullet_prefab()
+                prefabs
+                    .spawn("bullet", lazy_update.create_entity(&entities))
                     .with(Transform { position })
                     .with(PhysicsComponent {
                         velocity,
@@ -151,7 +193,9 @@ impl<'a> System<'a> for RunShotgunnerAI {
         Read<'a, SimTime>,
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Write<'a, SoundQueue>,
+        ReadStorage<'a, StatusEffects>,
     );
 
     fn run(
@@ -165,7 +209,9 @@ impl<'a> System<'a> for RunShotgunnerAI {
             sim_time,
             entities,
             lazy_update,
+            prefabs,
             mut sound_queue,
+            statuses,
         ): Self::SystemData,
     ) {
         let mut player_pos = Vector::new(0.0, 0.0);
@@ -173,9 +219,18 @@ impl<'a> System<'a> for RunShotgunnerAI {
             player_pos = player_transform.position;
         }
 
-        for (shotgunner, transform, physics, _) in
-            (&mut shotgunner_ais, &transforms, &mut physics, !&asleeps).join()
+        for (shotgunner, transform, physics, _, status) in (
+            &mut shotgunner_ais,
+            &transforms,
+            &mut physics,
+            !&asleeps,
+            statuses.maybe(),
+        )
+            .join()
         {
+            if status.map_or(false, |s| s.immobilised()) {
+                continue;
+            }
             let target_point =
                 player_pos + (transform.position - player_pos).with_len(TARGET_DISTANCE);
             let dir = target_point - transform.position;
@@ -191,9 +246,8 @@ impl<'a> System<'a> for RunShotgunnerAI {
                 let bullet_speed = 400.0;
                 let velocity = (player_pos - transform.position).with_len(bullet_speed);
                 let position = transform.position + velocity.with_len(30.0);
-                lazy_update
-                    .create_entity(&entities)
-                    .with_bullet_prefab()
+                prefabs
+                    .spawn("bullet", lazy_update.create_entity(&entities))
                     .with(Transform { position })
                     .with(PhysicsComponent {
                         velocity,
@@ -208,9 +262,8 @@ impl<'a> System<'a> for RunShotgunnerAI {
                     )
                     .with_len(bullet_speed);
                     let position = transform.position + velocity.with_len(30.0);
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_bullet_prefab()
+                    prefabs
+                        .spawn("bullet", lazy_update.create_entity(&entities))
                         .with(Transform { position })
                         .with(PhysicsComponent {
                             velocity,
@@ -224,9 +277,8 @@ impl<'a> System<'a> for RunShotgunnerAI {
                     )
                     .with_len(bullet_speed);
                     let position = transform.position + velocity.with_len(30.0);
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_bullet_prefab()
+                    prefabs
+                        .spawn("bullet", lazy_update.create_entity(&entities))
                         .with(Transform { position })
                         .with(PhysicsComponent {
                             velocity,
@@ -257,7 +309,9 @@ impl<'a> System<'a> for RunSpinnerAI {
         Read<'a, SimTime>,
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Write<'a, SoundQueue>,
+        ReadStorage<'a, StatusEffects>,
     );
 
     fn run(
@@ -271,7 +325,9 @@ impl<'a> System<'a> for RunSpinnerAI {
             sim_time,
             entities,
             lazy_update,
+            prefabs,
             mut sound_queue,
+            statuses,
         ): Self::SystemData,
     ) {
         let mut player_pos = Vector::new(0.0, 0.0);
@@ -279,9 +335,18 @@ impl<'a> System<'a> for RunSpinnerAI {
             player_pos = player_transform.position;
         }
 
-        for (spinner, transform, physics, _) in
-            (&mut spinner_ais, &transforms, &mut physics, !&asleeps).join()
+        for (spinner, transform, physics, _, status) in (
+            &mut spinner_ais,
+            &transforms,
+            &mut physics,
+            !&asleeps,
+            statuses.maybe(),
+        )
+            .join()
         {
+            if status.map_or(false, |s| s.immobilised()) {
+                continue;
+            }
             let target_point =
                 player_pos + (transform.position - player_pos).with_len(TARGET_DISTANCE);
             let dir = target_point - transform.position;
@@ -297,9 +362,8 @@ impl<'a> System<'a> for RunSpinnerAI {
                 let bullet_speed = 400.0;
                 let velocity = (player_pos - transform.position).with_len(bullet_speed);
                 let position = transform.position + velocity.with_len(30.0);
-                lazy_update
-                    .create_entity(&entities)
-                    .with_bullet_prefab()
+                prefabs
+                    .spawn("bullet", lazy_update.create_entity(&entities))
                     .with(Transform { position })
                     .with(PhysicsComponent {
                         velocity,
@@ -307,6 +371,12 @@ impl<'a> System<'a> for RunSpinnerAI {
                         ..Default::default()
                     })
                     .with(TeamWrap { team: Team::Enemy })
+                    .with(Bullet {
+                        radius: 5.0,
+                        damage: Damage::kinetic(1),
+                        effects: vec![StatusKind::Slow],
+                        ..Default::default()
+                    })
                     .build();
                 spinner.fire_cooldown.set(*sim_time, SPINNER_FIRE_COOLDOWN);
                 sound_queue.enqueue(SoundRequest::EnemyShot);
@@ -326,6 +396,7 @@ impl Component for Boss {
     type Storage = HashMapStorage<Self>;
 }
 
+#[derive(Copy, Clone, Debug, Deserialize)]
 pub enum BossAttack {
     Lines,
     Sideswipe,
@@ -340,15 +411,29 @@ impl<'a> System<'a> for RunBossAI {
         WriteStorage<'a, Boss>,
         Entities<'a>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Read<'a, SimTime>,
         Write<'a, SoundQueue>,
+        ReadStorage<'a, StatusEffects>,
     );
 
     fn run(
         &mut self,
-        (transforms, mut bosses, entities, lazy_update, sim_time, mut sound_queue): Self::SystemData,
+        (
+            transforms,
+            mut bosses,
+            entities,
+            lazy_update,
+            prefabs,
+            sim_time,
+            mut sound_queue,
+            statuses,
+        ): Self::SystemData,
     ) {
-        for (transform, boss) in (&transforms, &mut bosses).join() {
+        for (transform, boss, status) in (&transforms, &mut bosses, statuses.maybe()).join() {
+            if status.map_or(false, |s| s.immobilised()) {
+                continue;
+            }
             if boss.attack_cooldown.expired(*sim_time) {
                 match boss.attacks[boss.current_attack] {
                     BossAttack::Lines => {
@@ -360,9 +445,8 @@ impl<'a> System<'a> for RunBossAI {
                                     + Vector::from_angle(90.0 + angle1).with_len(70.0);
                                 let speed = 100.0 * (bullet as f32 + 1.0);
                                 let velocity = Vector::from_angle(90.0 + angle1).with_len(speed);
-                                lazy_update
-                                    .create_entity(&entities)
-                                    .with_bullet_prefab()
+                                prefabs
+                                    .spawn("bullet", lazy_update.create_entity(&entities))
                                     .with(Transform { position })
                                     .with(PhysicsComponent {
                                         velocity,
@@ -376,9 +460,8 @@ impl<'a> System<'a> for RunBossAI {
                                     + Vector::from_angle(90.0 + angle2).with_len(70.0);
                                 let speed = 100.0 * (bullet as f32 + 1.0);
                                 let velocity = Vector::from_angle(90.0 + angle2).with_len(speed);
-                                lazy_update
-                                    .create_entity(&entities)
-                                    .with_bullet_prefab()
+                                prefabs
+                                    .spawn("bullet", lazy_update.create_entity(&entities))
                                     .with(Transform { position })
                                     .with(PhysicsComponent {
                                         velocity,
@@ -397,9 +480,8 @@ impl<'a> System<'a> for RunBossAI {
                             let y = 100.0 * line as f32;
                             for bullet in 0..6 {
                                 let position = Vector::new(-500.0 + 20.0 * bullet as f32, y);
-                                lazy_update
-                                    .create_entity(&entities)
-                                    .with_bullet_prefab()
+                                prefabs
+                                    .spawn("bullet", lazy_update.create_entity(&entities))
                                     .with(Transform { position })
                                     .with(PhysicsComponent {
                                         velocity: Vector::new(speed, 0.0),
@@ -411,9 +493,8 @@ impl<'a> System<'a> for RunBossAI {
                                     .build();
                                 let y = y + 50.0;
                                 let position = Vector::new(500.0 - 20.0 * bullet as f32, y);
-                                lazy_update
-                                    .create_entity(&entities)
-                                    .with_bullet_prefab()
+                                prefabs
+                                    .spawn("bullet", lazy_update.create_entity(&entities))
                                     .with(Transform { position })
                                     .with(PhysicsComponent {
                                         velocity: Vector::new(-speed, 0.0),
@@ -431,9 +512,8 @@ impl<'a> System<'a> for RunBossAI {
                         for _b in 0..rng.gen_range(50, 60) {
                             let angle = rng.gen_range(0.0, 180.0);
                             let speed = rng.gen_range(100.0, 600.0);
-                            lazy_update
-                                .create_entity(&entities)
-                                .with_bullet_prefab()
+                            prefabs
+                                .spawn("bullet", lazy_update.create_entity(&entities))
                                 .with(Transform {
                                     position: transform.position,
                                 })
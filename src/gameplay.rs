@@ -1,14 +1,20 @@
-use crate::enemy_ai::{Boss, BossAttack};
-use crate::level_generation::{self, BOSS_ARENA_SIZE_Y};
+use crate::campaign::Campaign;
+use crate::damage::Resistances;
+use crate::enemy_ai::Boss;
+use crate::items::{compute_stats, Buff, Item, ItemRegistry};
+use crate::level_generation::{self, GenerationMetrics, BOSS_ARENA_SIZE_Y};
 use crate::physics::{
     hitbox_overlap, Bullet, CollidingWithWall, HitBox, PhysicsComponent, TileMap, TILE_SIZE,
 };
 use crate::player::PlayerControls;
 use crate::prelude::*;
-use crate::world_map::{CurrentDungeon, Dungeon, Item, Reward};
+use crate::render::{choice_cards, RenderComponent};
+use crate::status_effects::{apply_status, StatusEffects};
+use crate::world_map::{self, ChoiceOption, CurrentDungeon, Dungeon, Reward};
 use crate::{Camera, Event, EventQueue, Input, PlayerProgression, ScreenSize, UIState};
+use serde::Deserialize;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
 pub enum Team {
     Unaligned,
     Player,
@@ -100,103 +106,322 @@ impl Component for Exit {
 
 pub struct ExitSystem;
 
-// It's possible I should decompose this with an event being fired and handled elsewhere.
-// This has a lot of game logic stuffed into basically a collision check with the stairs...
 impl<'a> System<'a> for ExitSystem {
     type SystemData = (
-        WriteStorage<'a, Transform>,
+        ReadStorage<'a, Transform>,
         ReadStorage<'a, Exit>,
         ReadStorage<'a, PlayerControls>,
-        WriteStorage<'a, Camera>,
-        Write<'a, UIState>,
         ReadStorage<'a, HitBox>,
-        Write<'a, CurrentDungeon>,
-        WriteStorage<'a, Dungeon>,
+        Write<'a, EventQueue>,
+    );
+
+    fn run(&mut self, (transforms, exits, players, hitboxes, mut event_queue): Self::SystemData) {
+        let mut exit = false;
+        for (exit_transform, exit_hitbox, _) in (&transforms, &hitboxes, &exits).join() {
+            for (player_transform, player_hitbox, _) in (&transforms, &hitboxes, &players).join() {
+                if hitbox_overlap(player_transform, player_hitbox, exit_transform, exit_hitbox) {
+                    exit = true;
+                }
+            }
+        }
+        if exit {
+            event_queue.enqueue(Event::ExitReached);
+        }
+    }
+}
+
+// Clears out everything belonging to the level we're leaving.
+pub struct LevelTeardownSystem;
+
+impl<'a> System<'a> for LevelTeardownSystem {
+    type SystemData = (
+        Read<'a, EventQueue>,
         ReadStorage<'a, LevelObject>,
         Entities<'a>,
-        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, (event_queue, level_objects, entities): Self::SystemData) {
+        let leaving = event_queue.iter().any(|e| match e {
+            Event::ExitReached | Event::Retreat => true,
+            _ => false,
+        });
+        if leaving {
+            for (_, ent) in (&level_objects, &entities).join() {
+                entities.delete(ent).unwrap();
+            }
+        }
+    }
+}
+
+// Paid out for clearing the last floor of a dungeon, on top of its reward.
+const COMPLETION_GOLD: i32 = 10;
+
+// Takes the stairs down to the next floor, or finishes the dungeon on the last one.
+pub struct FloorTransitionSystem;
+
+impl<'a> System<'a> for FloorTransitionSystem {
+    type SystemData = (
+        Write<'a, EventQueue>,
+        Read<'a, CurrentDungeon>,
+        WriteStorage<'a, Dungeon>,
+        Write<'a, GenerationMetrics>,
         Write<'a, TileMap>,
-        Read<'a, SimTime>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+        ReadStorage<'a, PlayerControls>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Camera>,
         Read<'a, ScreenSize>,
+        Write<'a, PlayerProgression>,
+        WriteStorage<'a, Combative>,
+        Read<'a, HealthConfig>,
     );
 
     fn run(
         &mut self,
         (
-            mut transforms,
-            exits,
-            players,
-            mut cameras,
-            mut ui_state,
-            hitboxes,
+            mut event_queue,
             current_dungeon,
             mut dungeons,
-            level_objects,
+            mut generation_metrics,
+            mut tile_map,
             entities,
             lazy_update,
-            mut tile_map,
-            sim_time,
+            prefabs,
+            players,
+            mut transforms,
+            mut cameras,
             screen_size,
+            mut progression,
+            mut combatives,
+            health_config,
         ): Self::SystemData,
     ) {
-        let mut exit = false;
-        for (exit_transform, exit_hitbox, _) in (&transforms, &hitboxes, &exits).join() {
-            for (player_transform, player_hitbox, _) in (&transforms, &hitboxes, &players).join() {
-                if hitbox_overlap(player_transform, player_hitbox, exit_transform, exit_hitbox) {
-                    exit = true;
-                }
-            }
+        let exit = event_queue.iter().any(|e| match e {
+            Event::ExitReached => true,
+            _ => false,
+        });
+        if !exit {
+            return;
         }
-        if exit {
-            let current_dungeon = current_dungeon
-                .entity
-                .expect("We should be playing a dungeon when we hit an exit.");
-            let current_dungeon = dungeons
-                .get_mut(current_dungeon)
-                .expect("The current dungeon should be valid when hitting an exit.");
+        let current_dungeon = current_dungeon
+            .entity
+            .expect("We should be playing a dungeon when we hit an exit.");
+        let current_dungeon = dungeons
+            .get_mut(current_dungeon)
+            .expect("The current dungeon should be valid when hitting an exit.");
+        if current_dungeon.on_final_floor() {
             current_dungeon.completed = true;
-            for (_, ent) in (&level_objects, &entities).join() {
-                entities.delete(ent).unwrap();
+            progression.dungeons_completed += 1;
+            progression.currency += COMPLETION_GOLD * current_dungeon.difficulty;
+            for (_, c) in (&players, &mut combatives).join() {
+                c.heal(health_config.dungeon_complete_heal);
             }
-            match current_dungeon.reward {
-                Reward::Progress => {
-                    // Set up for boss fight
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_boss_prefab()
-                        .with(Transform {
-                            position: Vector::new(
-                                0.0,
-                                -(BOSS_ARENA_SIZE_Y as f32 - 2.0) * TILE_SIZE,
-                            ),
-                        })
-                        .with(Boss {
-                            attacks: vec![
-                                BossAttack::Lines,
-                                BossAttack::Sideswipe,
-                                BossAttack::RandomBurst,
-                            ],
-                            attack_cooldown: Timer::new_set(*sim_time, 3.0),
-                            ..Default::default()
-                        })
-                        .build();
-                    let dummy_camera_pos = lazy_update
-                        .create_entity(&entities)
-                        .with_dummy_prefab()
-                        .build();
-                    for (player_transform, _) in (&mut transforms, &players).join() {
-                        player_transform.position = Vector::new(0.0, 100.0);
-                    }
-                    for (camera_transform, camera) in (&mut transforms, &mut cameras).join() {
-                        camera_transform.position = Vector::new(0.0, 0.0) - screen_size.size / 2.0;
-                        camera.follow = dummy_camera_pos;
-                    }
-                    *tile_map = level_generation::make_boss_arena();
+            event_queue.enqueue(Event::DungeonCompleted);
+            return;
+        }
+
+        // The player keeps their health and items on the way down.
+        current_dungeon.current_floor += 1;
+        let level =
+            level_generation::generate_level(current_dungeon.style, &mut generation_metrics);
+        let start = world_map::spawn_floor(
+            &level,
+            current_dungeon.floor_difficulty(),
+            &entities,
+            &lazy_update,
+            &prefabs,
+        );
+        *tile_map = level.tile_map;
+        world_map::move_player(
+            start,
+            &entities,
+            &players,
+            &mut transforms,
+            &mut cameras,
+            &screen_size,
+        );
+    }
+}
+
+fn completed_dungeon<'a>(
+    event_queue: &EventQueue,
+    current_dungeon: &CurrentDungeon,
+    dungeons: &'a ReadStorage<Dungeon>,
+) -> Option<&'a Dungeon> {
+    if !event_queue.iter().any(|e| match e {
+        Event::DungeonCompleted => true,
+        _ => false,
+    }) {
+        return None;
+    }
+    let current_dungeon = current_dungeon
+        .entity
+        .expect("We should be playing a dungeon when we complete one.");
+    Some(
+        dungeons
+            .get(current_dungeon)
+            .expect("The current dungeon should be valid when completing it."),
+    )
+}
+
+pub struct RewardSystem;
+
+impl<'a> System<'a> for RewardSystem {
+    type SystemData = (
+        Read<'a, EventQueue>,
+        Read<'a, CurrentDungeon>,
+        ReadStorage<'a, Dungeon>,
+        Write<'a, UIState>,
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, PhysicsComponent>,
+        Write<'a, PlayerProgression>,
+        Read<'a, ItemRegistry>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            event_queue,
+            current_dungeon,
+            dungeons,
+            mut ui_state,
+            mut players,
+            mut combatives,
+            mut physics,
+            mut progression,
+            item_registry,
+        ): Self::SystemData,
+    ) {
+        let dungeon = match completed_dungeon(&event_queue, &current_dungeon, &dungeons) {
+            Some(dungeon) => dungeon,
+            None => return,
+        };
+        match dungeon.reward {
+            Reward::Choice(..)
+            | Reward::TripleChoice(..)
+            | Reward::Cursed(_)
+            | Reward::ActiveSwap(_) => {
+                *ui_state = UIState::Choice;
+            }
+            Reward::FullHeal => {
+                for (_, c) in (&players, &mut combatives).join() {
+                    c.heal_full();
                 }
-                Reward::Choice(_item1, _item2) => {
-                    *ui_state = UIState::Choice;
+                *ui_state = UIState::WorldMap;
+            }
+            Reward::MaxHpShard => {
+                for (p, c, physics) in (&mut players, &mut combatives, &mut physics).join() {
+                    p.base_stats.max_hp += 1;
+                    refresh_stats(p, c, physics, &item_registry);
                 }
+                *ui_state = UIState::WorldMap;
+            }
+            Reward::Currency(amount) => {
+                progression.currency += amount;
+                *ui_state = UIState::WorldMap;
             }
+            Reward::Progress => {}
+        }
+    }
+}
+
+// Dungeons that progress the game end in a boss fight rather than straight away.
+pub struct BossArenaSystem;
+
+impl<'a> System<'a> for BossArenaSystem {
+    type SystemData = (
+        Read<'a, EventQueue>,
+        Read<'a, CurrentDungeon>,
+        ReadStorage<'a, Dungeon>,
+        WriteStorage<'a, Transform>,
+        ReadStorage<'a, PlayerControls>,
+        WriteStorage<'a, Camera>,
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+        Write<'a, TileMap>,
+        Read<'a, SimTime>,
+        Read<'a, ScreenSize>,
+        Read<'a, Campaign>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            event_queue,
+            current_dungeon,
+            dungeons,
+            mut transforms,
+            players,
+            mut cameras,
+            entities,
+            lazy_update,
+            prefabs,
+            mut tile_map,
+            sim_time,
+            screen_size,
+            campaign,
+        ): Self::SystemData,
+    ) {
+        let tier = match completed_dungeon(&event_queue, &current_dungeon, &dungeons) {
+            Some(Dungeon {
+                reward: Reward::Progress,
+                tier,
+                ..
+            }) => *tier,
+            _ => return,
+        };
+        let boss = &campaign.tier(tier).boss;
+        prefabs
+            .spawn("boss", lazy_update.create_entity(&entities))
+            .with(Transform {
+                position: Vector::new(0.0, -(BOSS_ARENA_SIZE_Y as f32 - 2.0) * TILE_SIZE),
+            })
+            .with(Combative {
+                max_hp: boss.health,
+                ..Default::default()
+            })
+            .with(Boss {
+                attacks: boss.attacks.clone(),
+                attack_cooldown: Timer::new_set(*sim_time, 3.0),
+                ..Default::default()
+            })
+            .build();
+        let dummy_camera_pos = prefabs
+            .spawn("dummy", lazy_update.create_entity(&entities))
+            .build();
+        for (player_transform, _) in (&mut transforms, &players).join() {
+            player_transform.position = Vector::new(0.0, 100.0);
+        }
+        for (camera_transform, camera) in (&mut transforms, &mut cameras).join() {
+            camera_transform.position = Vector::new(0.0, 0.0) - screen_size.size / 2.0;
+            camera.follow = dummy_camera_pos;
+        }
+        *tile_map = level_generation::make_boss_arena();
+    }
+}
+
+// Leaving a dungeon early. It stays uncompleted so it can be tried again from the top.
+pub struct RetreatSystem;
+
+impl<'a> System<'a> for RetreatSystem {
+    type SystemData = (
+        Read<'a, EventQueue>,
+        Write<'a, CurrentDungeon>,
+        Write<'a, UIState>,
+    );
+
+    fn run(&mut self, (event_queue, mut current_dungeon, mut ui_state): Self::SystemData) {
+        let retreat = event_queue.iter().any(|e| match e {
+            Event::Retreat => true,
+            _ => false,
+        });
+        if retreat {
+            current_dungeon.entity = None;
+            *ui_state = UIState::WorldMap;
         }
     }
 }
@@ -207,6 +432,8 @@ const INVINCIBILITY_TIME: f32 = 0.75;
 pub struct Combative {
     pub max_hp: i32,
     pub damage: i32,
+    // Extra hit points on top of max_hp that are used up first and don't come back.
+    pub shield: i32,
     pub invincibility_cooldown: Timer,
 }
 
@@ -214,6 +441,77 @@ impl Component for Combative {
     type Storage = VecStorage<Self>;
 }
 
+impl Combative {
+    pub fn hp(&self) -> i32 {
+        self.max_hp - self.damage
+    }
+
+    // Returns true if this is the hit that killed it.
+    pub fn take_damage(&mut self, amount: i32) -> bool {
+        let absorbed = amount.min(self.shield);
+        self.shield -= absorbed;
+        let was_alive = self.damage < self.max_hp;
+        self.damage += amount - absorbed;
+        was_alive && self.damage >= self.max_hp
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.damage = (self.damage - amount).max(0);
+    }
+
+    pub fn heal_full(&mut self) {
+        self.heal(self.damage);
+    }
+}
+
+// Tuning for the healing the player gets outside of items.
+pub struct HealthConfig {
+    pub dungeon_complete_heal: i32,
+    pub boss_kill_heal: i32,
+    pub heart_heal: i32,
+    pub rest_heal: i32,
+    pub rest_price: i32,
+    pub max_shield: i32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            dungeon_complete_heal: 1,
+            boss_kill_heal: 1,
+            heart_heal: 1,
+            rest_heal: 2,
+            rest_price: 10,
+            max_shield: 3,
+        }
+    }
+}
+
+// Slowly heals players with regeneration, carrying over partial hit points between ticks.
+pub struct RegenerationSystem;
+
+impl<'a> System<'a> for RegenerationSystem {
+    type SystemData = (
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        Read<'a, SimTime>,
+    );
+
+    fn run(&mut self, (mut players, mut combatives, sim_time): Self::SystemData) {
+        for (p, c) in (&mut players, &mut combatives).join() {
+            if c.damage == 0 || c.damage >= c.max_hp {
+                p.regen_progress = 0.0;
+                continue;
+            }
+            p.regen_progress += p.stats.regen * sim_time.dt;
+            if p.regen_progress >= 1.0 {
+                p.regen_progress -= 1.0;
+                c.heal(1);
+            }
+        }
+    }
+}
+
 pub struct CombativeCollisionHandler;
 
 impl<'a> System<'a> for CombativeCollisionHandler {
@@ -223,11 +521,21 @@ impl<'a> System<'a> for CombativeCollisionHandler {
         WriteStorage<'a, Combative>,
         Read<'a, SimTime>,
         ReadStorage<'a, Bullet>,
+        WriteStorage<'a, StatusEffects>,
+        ReadStorage<'a, Resistances>,
     );
 
     fn run(
         &mut self,
-        (entities, mut event_queue, mut combatives, sim_time, bullets): Self::SystemData,
+        (
+            entities,
+            mut event_queue,
+            mut combatives,
+            sim_time,
+            bullets,
+            mut statuses,
+            resistances,
+        ): Self::SystemData,
     ) {
         let mut new_events = Vec::new();
         for event in event_queue.iter() {
@@ -236,10 +544,12 @@ impl<'a> System<'a> for CombativeCollisionHandler {
                     let bullet = bullets.get(*bullet_ent).unwrap();
                     let c = combatives.get_mut(*entity).unwrap();
                     if c.invincibility_cooldown.expired(*sim_time) {
-                        c.damage += bullet.damage;
-                        if c.damage >= c.max_hp {
+                        if c.take_damage(bullet.damage.against(resistances.get(*entity))) {
                             new_events.push(Event::EntityKilled(*entity));
                         }
+                        for effect in bullet.effects.iter() {
+                            apply_status(*entity, *effect, &mut statuses, *sim_time);
+                        }
                         if !bullet.penetrating {
                             entities.delete(*bullet_ent).unwrap();
                         }
@@ -254,57 +564,48 @@ impl<'a> System<'a> for CombativeCollisionHandler {
     }
 }
 
-fn apply_upgrade<'a>(
+// Call after changing the player's items or base stats.
+fn refresh_stats(
+    p: &mut PlayerControls,
+    c: &mut Combative,
+    physics: &mut PhysicsComponent,
+    item_registry: &ItemRegistry,
+) {
+    let buffs: Vec<_> = p.buffs.iter().map(|(buff, _)| *buff).collect();
+    p.stats = compute_stats(p.base_stats, &p.items_acquired, &buffs, item_registry);
+    c.max_hp = p.stats.max_hp;
+    physics.max_speed = p.stats.max_speed;
+}
+
+// Shown on the world map until the next dungeon is entered.
+#[derive(Default)]
+pub struct Announcement {
+    pub text: Option<String>,
+}
+
+pub fn apply_upgrade<'a>(
     item: Item,
+    item_registry: &ItemRegistry,
     players: &mut WriteStorage<'a, PlayerControls>,
     combatives: &mut WriteStorage<'a, Combative>,
     physics: &mut WriteStorage<'a, PhysicsComponent>,
+    announcement: &mut Announcement,
 ) {
-    for p in (players).join() {
+    for (p, c, physics) in (players, combatives, physics).join() {
+        let before: Vec<_> = item_registry
+            .synergies(&p.items_acquired)
+            .map(|s| s.name.clone())
+            .collect();
         p.items_acquired.push(item);
-    }
-
-    match item {
-        Item::AttackSpeed => {
-            for p in (players).join() {
-                p.fire_rate -= 0.1;
-            }
-        }
-        Item::MaxHealth => {
-            for (_, c) in (players, combatives).join() {
-                c.max_hp += 1;
-            }
-        }
-        Item::TripleShot => {
-            for p in (players).join() {
-                p.triple_shot = true;
-            }
-        }
-        Item::ExtraDamage => {
-            for p in (players).join() {
-                p.bullet_damage += 1;
-            }
-        }
-        Item::Penetrating => {
-            for p in (players).join() {
-                p.penetrating = true;
-            }
-        }
-        Item::ReduceDodgeCooldown => {
-            for p in (players).join() {
-                p.dodge_cooldown_time = 0.9;
-            }
-        }
-        Item::Backfire => {
-            for p in (players).join() {
-                p.backfire = true;
-            }
-        }
-        Item::SpeedIncrease => {
-            for (physics, _) in (physics, players).join() {
-                physics.max_speed = 300.0;
+        for synergy in item_registry.synergies(&p.items_acquired) {
+            if !before.contains(&synergy.name) {
+                announcement.text = Some(format!(
+                    "Synergy! {}: {}",
+                    synergy.name, synergy.description
+                ));
             }
         }
+        refresh_stats(p, c, physics, item_registry);
     }
 }
 
@@ -320,6 +621,8 @@ impl<'a> System<'a> for ChoiceSystem {
         WriteStorage<'a, Combative>,
         WriteStorage<'a, Dungeon>,
         WriteStorage<'a, PhysicsComponent>,
+        Read<'a, ItemRegistry>,
+        Write<'a, Announcement>,
     );
 
     fn run(
@@ -333,6 +636,8 @@ impl<'a> System<'a> for ChoiceSystem {
             mut combatives,
             dungeons,
             mut physics,
+            item_registry,
+            mut announcement,
         ): Self::SystemData,
     ) {
         let current_dungeon = current_dungeon
@@ -341,20 +646,35 @@ impl<'a> System<'a> for ChoiceSystem {
         let current_dungeon = dungeons
             .get(current_dungeon)
             .expect("The current dungeon should be valid when are doing choice.");
-        let mouse_pos = input.raw_mouse_pos;
         if input.clicked {
-            if let Reward::Choice(item1, item2) = current_dungeon.reward {
-                if mouse_pos.x > screen_size.size.x / 2.0 {
-                    apply_upgrade(item2, &mut players, &mut combatives, &mut physics);
-                } else {
-                    apply_upgrade(item1, &mut players, &mut combatives, &mut physics);
-                }
-                for (_, c) in (&players, &mut combatives).join() {
-                    c.damage = (c.damage - 1).max(0);
+            let options = current_dungeon.reward.options();
+            if options.is_empty() {
+                panic!("Bad choice state");
+            }
+            let cards = choice_cards(screen_size.size, options.len());
+            let chosen = cards
+                .iter()
+                .zip(options.iter())
+                .find(|(card, _)| card.contains(input.raw_mouse_pos));
+            if let Some((_, option)) = chosen {
+                match *option {
+                    ChoiceOption::Item(item) => apply_upgrade(
+                        item,
+                        &item_registry,
+                        &mut players,
+                        &mut combatives,
+                        &mut physics,
+                        &mut announcement,
+                    ),
+                    ChoiceOption::Active(active) => {
+                        for p in (&mut players).join() {
+                            p.active_item = Some(active);
+                            p.active_cooldown = Timer::default();
+                        }
+                    }
+                    ChoiceOption::KeepActive => {}
                 }
                 *ui_state = UIState::WorldMap;
-            } else {
-                panic!("Bad choice state");
             }
         }
     }
@@ -373,6 +693,8 @@ impl<'a> System<'a> for BossDeathSystem {
         ReadStorage<'a, PlayerControls>,
         WriteStorage<'a, Combative>,
         Write<'a, SoundQueue>,
+        Read<'a, HealthConfig>,
+        Read<'a, Campaign>,
     );
 
     fn run(
@@ -387,6 +709,8 @@ impl<'a> System<'a> for BossDeathSystem {
             players,
             mut combatives,
             mut sound_queue,
+            health_config,
+            campaign,
         ): Self::SystemData,
     ) {
         for event in event_queue.iter() {
@@ -396,16 +720,219 @@ impl<'a> System<'a> for BossDeathSystem {
                     for (ent, _) in (&entities, &level_objects).join() {
                         entities.delete(ent).unwrap();
                     }
-                    if !progress.range_extended {
-                        progress.range_extended = true;
+                    // Each boss unlocks the next tier of oases, until there are none left.
+                    if !campaign.is_final_tier(progress.tier) {
+                        progress.tier += 1;
                         *ui_state = UIState::WorldMap;
                     } else {
                         *ui_state = UIState::Victory;
                     }
                     for (_, c) in (&players, &mut combatives).join() {
-                        c.damage = (c.damage - 1).max(0);
+                        c.heal(health_config.boss_kill_heal);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum PickupKind {
+    Heart,
+    Shield,
+    Gold(i32),
+    Buff(Buff),
+}
+
+impl Default for PickupKind {
+    fn default() -> Self {
+        PickupKind::Heart
+    }
+}
+
+#[derive(Default)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+impl Component for Pickup {
+    type Storage = HashMapStorage<Self>;
+}
+
+// Drops left lying around disappear after this long.
+const DROP_LIFETIME: f32 = 10.0;
+
+// What an enemy might drop when it dies. Each chance is rolled separately.
+#[derive(Default)]
+pub struct Loot {
+    pub heart_chance: f32,
+    pub shield_chance: f32,
+    pub gold_chance: f32,
+    pub gold: i32,
+    pub buff_chance: f32,
+}
+
+impl Component for Loot {
+    type Storage = HashMapStorage<Self>;
+}
+
+pub fn drop_loot(
+    loot: &Loot,
+    position: Vector,
+    sim_time: SimTime,
+    entities: &Entities,
+    lazy_update: &LazyUpdate,
+    prefabs: &PrefabRegistry,
+) {
+    let mut rng = rand::thread_rng();
+    let mut drops = Vec::new();
+    if rng.gen_range(0.0, 1.0) < loot.heart_chance {
+        drops.push(PickupKind::Heart);
+    }
+    if rng.gen_range(0.0, 1.0) < loot.shield_chance {
+        drops.push(PickupKind::Shield);
+    }
+    if rng.gen_range(0.0, 1.0) < loot.gold_chance {
+        drops.push(PickupKind::Gold(loot.gold));
+    }
+    if rng.gen_range(0.0, 1.0) < loot.buff_chance {
+        let buffs = Buff::all();
+        drops.push(PickupKind::Buff(buffs[rng.gen_range(0, buffs.len())]));
+    }
+    // Spread multiple drops out a little so they don't sit on top of each other.
+    let n_drops = drops.len();
+    for (i, kind) in drops.into_iter().enumerate() {
+        let offset = if n_drops > 1 {
+            Vector::from_angle(i as f32 * 360.0 / n_drops as f32).with_len(12.0)
+        } else {
+            Vector::ZERO
+        };
+        let builder = lazy_update.create_entity(entities);
+        let builder = match kind {
+            PickupKind::Heart => prefabs.spawn("heart", builder),
+            PickupKind::Shield => prefabs.spawn("shield", builder),
+            PickupKind::Gold(_) => prefabs.spawn("gold", builder),
+            PickupKind::Buff(buff) => prefabs.spawn("buff", builder).with(RenderComponent {
+                radius: 7.0,
+                colour: buff.colour(),
+            }),
+        };
+        builder
+            .with(Pickup { kind })
+            .with(Transform {
+                position: position + offset,
+            })
+            .with(Lifetime {
+                expires: Timer::new_set(sim_time, DROP_LIFETIME),
+            })
+            .build();
+    }
+}
+
+pub struct PickupSystem;
+
+impl<'a> System<'a> for PickupSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Pickup>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, HitBox>,
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, PhysicsComponent>,
+        Write<'a, PlayerProgression>,
+        Read<'a, ItemRegistry>,
+        Read<'a, SimTime>,
+        Read<'a, HealthConfig>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            pickups,
+            transforms,
+            hitboxes,
+            mut players,
+            mut combatives,
+            mut physics,
+            mut progression,
+            item_registry,
+            sim_time,
+            health_config,
+        ): Self::SystemData,
+    ) {
+        for (player_transform, player_hitbox, p, c, physics) in (
+            &transforms,
+            &hitboxes,
+            &mut players,
+            &mut combatives,
+            &mut physics,
+        )
+            .join()
+        {
+            for (pickup_ent, pickup, pickup_transform, pickup_hitbox) in
+                (&entities, &pickups, &transforms, &hitboxes).join()
+            {
+                if !hitbox_overlap(
+                    player_transform,
+                    player_hitbox,
+                    pickup_transform,
+                    pickup_hitbox,
+                ) {
+                    continue;
+                }
+                match pickup.kind {
+                    PickupKind::Heart => {
+                        // Leave hearts lying around for later if we're already at full health.
+                        if c.damage == 0 {
+                            continue;
+                        }
+                        c.heal(health_config.heart_heal);
+                    }
+                    PickupKind::Shield => {
+                        if c.shield >= health_config.max_shield {
+                            continue;
+                        }
+                        c.shield += 1;
+                    }
+                    PickupKind::Gold(amount) => progression.currency += amount,
+                    PickupKind::Buff(buff) => {
+                        // Picking up a buff we already have just extends it.
+                        p.buffs.retain(|(b, _)| *b != buff);
+                        p.buffs
+                            .push((buff, Timer::new_set(*sim_time, buff.duration())));
+                        refresh_stats(p, c, physics, &item_registry);
                     }
                 }
+                entities
+                    .delete(pickup_ent)
+                    .expect("We just got this entity out so it should be valid.");
+            }
+        }
+    }
+}
+
+pub struct BuffExpirySystem;
+
+impl<'a> System<'a> for BuffExpirySystem {
+    type SystemData = (
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, PhysicsComponent>,
+        Read<'a, ItemRegistry>,
+        Read<'a, SimTime>,
+    );
+
+    fn run(
+        &mut self,
+        (mut players, mut combatives, mut physics, item_registry, sim_time): Self::SystemData,
+    ) {
+        for (p, c, physics) in (&mut players, &mut combatives, &mut physics).join() {
+            let n_buffs = p.buffs.len();
+            p.buffs.retain(|(_, expires)| !expires.expired(*sim_time));
+            if p.buffs.len() != n_buffs {
+                refresh_stats(p, c, physics, &item_registry);
             }
         }
     }
@@ -443,3 +970,78 @@ impl<'a> System<'a> for SleepSystem {
         }
     }
 }
+
+// Deletes the entity once `expires` passes.
+#[derive(Default)]
+pub struct Lifetime {
+    pub expires: Timer,
+}
+
+impl Component for Lifetime {
+    type Storage = VecStorage<Self>;
+}
+
+pub struct LifetimeSystem;
+
+impl<'a> System<'a> for LifetimeSystem {
+    type SystemData = (Entities<'a>, ReadStorage<'a, Lifetime>, Read<'a, SimTime>);
+
+    fn run(&mut self, (entities, lifetimes, sim_time): Self::SystemData) {
+        for (entity, lifetime) in (&entities, &lifetimes).join() {
+            if lifetime.expires.expired(*sim_time) {
+                entities
+                    .delete(entity)
+                    .expect("We just got this entity out so it should be valid.");
+            }
+        }
+    }
+}
+
+const FIRE_DROP_INTERVAL: f32 = 0.05;
+const FIRE_LIFETIME: f32 = 1.5;
+
+// Bullets with this leave patches of fire behind them.
+#[derive(Default)]
+pub struct BurningTrail {
+    pub next_drop: Timer,
+}
+
+impl Component for BurningTrail {
+    type Storage = HashMapStorage<Self>;
+}
+
+pub struct BurningTrailSystem;
+
+impl<'a> System<'a> for BurningTrailSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, BurningTrail>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, TeamWrap>,
+        Read<'a, SimTime>,
+        Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut trails, transforms, teams, sim_time, lazy_update, prefabs): Self::SystemData,
+    ) {
+        for (trail, transform, team) in (&mut trails, &transforms, &teams).join() {
+            if !trail.next_drop.expired(*sim_time) {
+                continue;
+            }
+            trail.next_drop.set(*sim_time, FIRE_DROP_INTERVAL);
+            prefabs
+                .spawn("fire", lazy_update.create_entity(&entities))
+                .with(Transform {
+                    position: transform.position,
+                })
+                .with(TeamWrap { team: team.team })
+                .with(Lifetime {
+                    expires: Timer::new_set(*sim_time, FIRE_LIFETIME),
+                })
+                .build();
+        }
+    }
+}
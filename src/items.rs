@@ -0,0 +1,398 @@
+use crate::damage::{Damage, DamageType};
+use quicksilver::graphics::Color;
+use rand::Rng;
+use serde::Deserialize;
+
+// Items are defined in items.ron. Players gain stats only through modifiers, which are recomputed
+// from the base stats whenever the inventory changes so that everything stacks.
+const ITEMS_SOURCE: (&str, &str) = ("items.ron", include_str!("../items.ron"));
+const SYNERGIES_SOURCE: (&str, &str) = ("synergies.ron", include_str!("../synergies.ron"));
+// Rewards offer up to three different items at once, and cursed rewards draw one of their own.
+const MIN_ITEMS: usize = 3;
+const MIN_CURSED_ITEMS: usize = 1;
+
+// Stops stacked fire rate items from making the gun fire every frame.
+const MIN_FIRE_RATE: f32 = 0.1;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum Stat {
+    FireRate,
+    BulletDamage,
+    DodgeCooldown,
+    MaxSpeed,
+    MaxHp,
+    DashDistance,
+    DashIFrames,
+    Regen,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum Flag {
+    TripleShot,
+    Penetrating,
+    Backfire,
+    Venomous,
+    Freezing,
+    PitDash,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Modifier {
+    Add(Stat, f32),
+    Multiply(Stat, f32),
+    Set(Flag),
+    // Bullet damage becomes this type instead of kinetic.
+    Convert(DamageType),
+    // Extra damage of this type on every bullet.
+    AddDamage(DamageType, i32),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+}
+
+impl Rarity {
+    // Relative chance of being offered.
+    pub fn weight(self) -> u32 {
+        match self {
+            Rarity::Common => 6,
+            Rarity::Uncommon => 3,
+            Rarity::Rare => 1,
+        }
+    }
+
+    // Base price in the oasis shop.
+    pub fn price(self) -> i32 {
+        match self {
+            Rarity::Common => 20,
+            Rarity::Uncommon => 35,
+            Rarity::Rare => 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemData {
+    pub name: String,
+    pub description: String,
+    colour: (u8, u8, u8),
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub cursed: bool,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl ItemData {
+    pub fn colour(&self) -> Color {
+        rgba!(self.colour.0, self.colour.1, self.colour.2, 1.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum SynergyEffect {
+    RingShot,
+    BurningTrail,
+}
+
+// Extra effects unlocked by holding a particular set of items.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Synergy {
+    pub name: String,
+    pub description: String,
+    requires: Vec<String>,
+    effects: Vec<SynergyEffect>,
+}
+
+// An index into the ItemRegistry.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Item(pub usize);
+
+#[derive(Default)]
+pub struct ItemRegistry {
+    items: Vec<ItemData>,
+    synergies: Vec<Synergy>,
+}
+
+impl ItemRegistry {
+    pub fn load() -> ItemRegistry {
+        let (file, source) = ITEMS_SOURCE;
+        let items: Vec<ItemData> = ron::de::from_str(source)
+            .unwrap_or_else(|e| panic!("Bad item definitions {}:{}", file, e));
+        let uncursed = items.iter().filter(|i| !i.cursed).count();
+        let cursed = items.len() - uncursed;
+        if uncursed < MIN_ITEMS || cursed < MIN_CURSED_ITEMS {
+            panic!(
+                "Bad item definitions {}: need {} items and {} cursed items, found {} and {}",
+                file, MIN_ITEMS, MIN_CURSED_ITEMS, uncursed, cursed
+            );
+        }
+        let (file, source) = SYNERGIES_SOURCE;
+        let synergies: Vec<Synergy> = ron::de::from_str(source)
+            .unwrap_or_else(|e| panic!("Bad synergy definitions {}:{}", file, e));
+        for synergy in synergies.iter() {
+            for name in synergy.requires.iter() {
+                if !items.iter().any(|i| i.name == *name) {
+                    panic!(
+                        "Bad synergy definitions {} ({}): no item called '{}'",
+                        file, synergy.name, name
+                    );
+                }
+            }
+        }
+        ItemRegistry { items, synergies }
+    }
+
+    // Synergies unlocked by holding `items`.
+    pub fn synergies<'a>(&'a self, items: &'a [Item]) -> impl Iterator<Item = &'a Synergy> + 'a {
+        self.synergies.iter().filter(move |synergy| {
+            synergy
+                .requires
+                .iter()
+                .all(|name| items.iter().any(|item| self.get(*item).name == *name))
+        })
+    }
+
+    pub fn get(&self, item: Item) -> &ItemData {
+        &self.items[item.0]
+    }
+
+    pub fn all(&self) -> Vec<Item> {
+        (0..self.items.len()).map(Item).collect()
+    }
+
+    // Draws `count` different items weighted by rarity. Nothing is used up, so later rewards can
+    // offer the same items again.
+    pub fn draw(&self, cursed: bool, count: usize, rng: &mut impl Rng) -> Vec<Item> {
+        let mut pool: Vec<_> = self
+            .all()
+            .into_iter()
+            .filter(|item| self.get(*item).cursed == cursed)
+            .collect();
+        let mut out = Vec::new();
+        while out.len() < count && !pool.is_empty() {
+            let total: u32 = pool.iter().map(|i| self.get(*i).rarity.weight()).sum();
+            let mut roll = rng.gen_range(0, total);
+            let index = pool
+                .iter()
+                .position(|i| {
+                    let weight = self.get(*i).rarity.weight();
+                    if roll < weight {
+                        true
+                    } else {
+                        roll -= weight;
+                        false
+                    }
+                })
+                .unwrap();
+            out.push(pool.remove(index));
+        }
+        out
+    }
+}
+
+// Temporary boosts from pickups, applied on top of the item modifiers while they last.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum Buff {
+    Frenzy,
+    Might,
+    Haste,
+}
+
+impl Buff {
+    pub fn all() -> &'static [Buff] {
+        &[Buff::Frenzy, Buff::Might, Buff::Haste]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Buff::Frenzy => "Frenzy",
+            Buff::Might => "Might",
+            Buff::Haste => "Haste",
+        }
+    }
+
+    pub fn colour(self) -> Color {
+        match self {
+            Buff::Frenzy => rgba!(255, 140, 0, 1.0),
+            Buff::Might => rgba!(200, 30, 30, 1.0),
+            Buff::Haste => rgba!(80, 200, 255, 1.0),
+        }
+    }
+
+    pub fn duration(self) -> f32 {
+        match self {
+            Buff::Frenzy => 8.0,
+            Buff::Might => 8.0,
+            Buff::Haste => 10.0,
+        }
+    }
+
+    fn modifier(self) -> Modifier {
+        match self {
+            Buff::Frenzy => Modifier::Multiply(Stat::FireRate, 0.5),
+            Buff::Might => Modifier::Add(Stat::BulletDamage, 1.0),
+            Buff::Haste => Modifier::Multiply(Stat::MaxSpeed, 1.4),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PlayerStats {
+    pub fire_rate: f32,
+    pub bullet_damage: i32,
+    pub dodge_cooldown_time: f32,
+    pub max_speed: f32,
+    pub max_hp: i32,
+    // Pixels covered by a dash, and how long the player is invulnerable after starting one.
+    pub dash_distance: f32,
+    pub dash_iframes: f32,
+    // Hit points regenerated per second.
+    pub regen: f32,
+    pub triple_shot: bool,
+    pub penetrating: bool,
+    pub backfire: bool,
+    pub ring_shot: bool,
+    pub burning_trail: bool,
+    pub venomous: bool,
+    pub freezing: bool,
+    pub dash_over_pits: bool,
+    pub damage_conversion: Option<DamageType>,
+    pub extra_damage: Damage,
+}
+
+impl PlayerStats {
+    pub const BASE: PlayerStats = PlayerStats {
+        fire_rate: 0.7,
+        bullet_damage: 1,
+        dodge_cooldown_time: 2.0,
+        max_speed: 200.0,
+        max_hp: 3,
+        dash_distance: 75.0,
+        dash_iframes: 0.25,
+        regen: 0.0,
+        triple_shot: false,
+        penetrating: false,
+        backfire: false,
+        ring_shot: false,
+        burning_trail: false,
+        venomous: false,
+        freezing: false,
+        dash_over_pits: false,
+        damage_conversion: None,
+        extra_damage: Damage {
+            kinetic: 0,
+            fire: 0,
+            arcane: 0,
+        },
+    };
+
+    // What each of the player's bullets deals.
+    pub fn damage(&self) -> Damage {
+        let mut damage = self.extra_damage;
+        damage.add(
+            self.damage_conversion.unwrap_or(DamageType::Kinetic),
+            self.bullet_damage,
+        );
+        damage
+    }
+
+    fn get(&self, stat: Stat) -> f32 {
+        match stat {
+            Stat::FireRate => self.fire_rate,
+            Stat::BulletDamage => self.bullet_damage as f32,
+            Stat::DodgeCooldown => self.dodge_cooldown_time,
+            Stat::MaxSpeed => self.max_speed,
+            Stat::MaxHp => self.max_hp as f32,
+            Stat::DashDistance => self.dash_distance,
+            Stat::DashIFrames => self.dash_iframes,
+            Stat::Regen => self.regen,
+        }
+    }
+
+    fn set(&mut self, stat: Stat, value: f32) {
+        match stat {
+            Stat::FireRate => self.fire_rate = value.max(MIN_FIRE_RATE),
+            Stat::BulletDamage => self.bullet_damage = value.round().max(1.0) as i32,
+            Stat::DodgeCooldown => self.dodge_cooldown_time = value.max(0.0),
+            Stat::MaxSpeed => self.max_speed = value.max(0.0),
+            Stat::MaxHp => self.max_hp = value.round().max(1.0) as i32,
+            Stat::DashDistance => self.dash_distance = value.max(0.0),
+            Stat::DashIFrames => self.dash_iframes = value.max(0.0),
+            Stat::Regen => self.regen = value.max(0.0),
+        }
+    }
+
+    fn set_flag(&mut self, flag: Flag) {
+        match flag {
+            Flag::TripleShot => self.triple_shot = true,
+            Flag::Penetrating => self.penetrating = true,
+            Flag::Backfire => self.backfire = true,
+            Flag::Venomous => self.venomous = true,
+            Flag::Freezing => self.freezing = true,
+            Flag::PitDash => self.dash_over_pits = true,
+        }
+    }
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        PlayerStats::BASE
+    }
+}
+
+pub fn compute_stats(
+    base: PlayerStats,
+    items: &[Item],
+    buffs: &[Buff],
+    registry: &ItemRegistry,
+) -> PlayerStats {
+    let modifiers: Vec<Modifier> = items
+        .iter()
+        .flat_map(|item| registry.get(*item).modifiers.iter().cloned())
+        .chain(buffs.iter().map(|buff| buff.modifier()))
+        .collect();
+    let mut stats = base;
+    for stat in [
+        Stat::FireRate,
+        Stat::BulletDamage,
+        Stat::DodgeCooldown,
+        Stat::MaxSpeed,
+        Stat::MaxHp,
+        Stat::DashDistance,
+        Stat::DashIFrames,
+        Stat::Regen,
+    ]
+    .iter()
+    {
+        let mut added = 0.0;
+        let mut multiplier = 1.0;
+        for modifier in modifiers.iter() {
+            match *modifier {
+                Modifier::Add(s, amount) if s == *stat => added += amount,
+                Modifier::Multiply(s, factor) if s == *stat => multiplier *= factor,
+                _ => {}
+            }
+        }
+        stats.set(*stat, (base.get(*stat) + added) * multiplier);
+    }
+    for modifier in modifiers.iter() {
+        match *modifier {
+            Modifier::Set(flag) => stats.set_flag(flag),
+            Modifier::Convert(damage_type) => stats.damage_conversion = Some(damage_type),
+            Modifier::AddDamage(damage_type, amount) => stats.extra_damage.add(damage_type, amount),
+            _ => {}
+        }
+    }
+    for synergy in registry.synergies(items) {
+        for effect in synergy.effects.iter() {
+            match effect {
+                SynergyEffect::RingShot => stats.ring_shot = true,
+                SynergyEffect::BurningTrail => stats.burning_trail = true,
+            }
+        }
+    }
+    stats
+}
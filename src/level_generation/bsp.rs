@@ -0,0 +1,220 @@
+use super::{manhatten_distance, random_enemy_type, GeneratedLevel, GenerationError, FLOOR, WALL};
+use crate::physics::TileMap;
+use crate::prelude::*;
+
+const LEVEL_SIZE: i32 = 64;
+const MIN_LEAF_SIZE: i32 = 10;
+const MAX_LEAF_SIZE: i32 = 24;
+const MIN_ROOM_SIZE: i32 = 5;
+const CORRIDOR_WIDTH: i32 = 2;
+
+#[derive(Copy, Clone, Debug)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+impl Rect {
+    fn centre(self) -> (i32, i32) {
+        (self.x + self.w / 2, self.y + self.h / 2)
+    }
+}
+
+// Each node of the partition either has two children or (as a leaf) a single room.
+struct Node {
+    area: Rect,
+    children: Option<(Box<Node>, Box<Node>)>,
+    room: Option<Rect>,
+}
+
+fn split(area: Rect, rng: &mut impl Rng) -> Node {
+    let too_big = area.w > MAX_LEAF_SIZE || area.h > MAX_LEAF_SIZE;
+    let can_split_x = area.w >= MIN_LEAF_SIZE * 2;
+    let can_split_y = area.h >= MIN_LEAF_SIZE * 2;
+    // Big leaves always split, smaller ones only sometimes, which gives the variety in room sizes.
+    if (!can_split_x && !can_split_y) || (!too_big && rng.gen_range(0.0, 1.0) > 0.75) {
+        return Node {
+            area,
+            children: None,
+            room: None,
+        };
+    }
+
+    // Prefer cutting across the long axis so leaves don't end up as thin strips.
+    let split_vertically = if !can_split_y {
+        true
+    } else if !can_split_x {
+        false
+    } else if area.w as f32 / area.h as f32 >= 1.25 {
+        true
+    } else if area.h as f32 / area.w as f32 >= 1.25 {
+        false
+    } else {
+        rng.gen_range(0.0, 1.0) > 0.5
+    };
+
+    let (a, b) = if split_vertically {
+        let cut = rng.gen_range(MIN_LEAF_SIZE, area.w - MIN_LEAF_SIZE + 1);
+        (
+            Rect { w: cut, ..area },
+            Rect {
+                x: area.x + cut,
+                w: area.w - cut,
+                ..area
+            },
+        )
+    } else {
+        let cut = rng.gen_range(MIN_LEAF_SIZE, area.h - MIN_LEAF_SIZE + 1);
+        (
+            Rect { h: cut, ..area },
+            Rect {
+                y: area.y + cut,
+                h: area.h - cut,
+                ..area
+            },
+        )
+    };
+    Node {
+        area,
+        children: Some((Box::new(split(a, rng)), Box::new(split(b, rng)))),
+        room: None,
+    }
+}
+
+fn place_rooms(node: &mut Node, rng: &mut impl Rng, rooms: &mut Vec<Rect>) {
+    match node.children {
+        Some((ref mut a, ref mut b)) => {
+            place_rooms(a, rng, rooms);
+            place_rooms(b, rng, rooms);
+        }
+        None => {
+            // Leave at least a one tile wall between the room and the edge of the leaf.
+            let w = rng.gen_range(MIN_ROOM_SIZE, node.area.w - 1);
+            let h = rng.gen_range(MIN_ROOM_SIZE, node.area.h - 1);
+            let x = node.area.x + rng.gen_range(1, node.area.w - w);
+            let y = node.area.y + rng.gen_range(1, node.area.h - h);
+            let room = Rect { x, y, w, h };
+            node.room = Some(room);
+            rooms.push(room);
+        }
+    }
+}
+
+fn any_room(node: &Node, rng: &mut impl Rng) -> Rect {
+    match node.children {
+        Some((ref a, ref b)) => {
+            if rng.gen_range(0.0, 1.0) > 0.5 {
+                any_room(a, rng)
+            } else {
+                any_room(b, rng)
+            }
+        }
+        None => node
+            .room
+            .expect("Rooms should be placed before connecting them."),
+    }
+}
+
+fn carve(tile_map: &mut TileMap, x: i32, y: i32) {
+    tile_map.tiles.insert((x, y), FLOOR);
+}
+
+fn carve_corridor(from: (i32, i32), to: (i32, i32), rng: &mut impl Rng, tile_map: &mut TileMap) {
+    // L-shaped: go along one axis then the other, picking the bend at random.
+    let corner = if rng.gen_range(0.0, 1.0) > 0.5 {
+        (to.0, from.1)
+    } else {
+        (from.0, to.1)
+    };
+    for &(start, end) in [(from, corner), (corner, to)].iter() {
+        for x in start.0.min(end.0)..=start.0.max(end.0) {
+            for y in start.1.min(end.1)..=start.1.max(end.1) {
+                for w in 0..CORRIDOR_WIDTH {
+                    for h in 0..CORRIDOR_WIDTH {
+                        carve(tile_map, x + w, y + h);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn connect(node: &Node, rng: &mut impl Rng, tile_map: &mut TileMap) {
+    if let Some((ref a, ref b)) = node.children {
+        connect(a, rng, tile_map);
+        connect(b, rng, tile_map);
+        let from = any_room(a, rng).centre();
+        let to = any_room(b, rng).centre();
+        carve_corridor(from, to, rng, tile_map);
+    }
+}
+
+pub fn try_generate_level<R: Rng>(rng: &mut R) -> Result<GeneratedLevel, GenerationError> {
+    let mut tile_map: TileMap = Default::default();
+    for x in -1..=LEVEL_SIZE {
+        for y in -1..=LEVEL_SIZE {
+            tile_map.tiles.insert((x, y), WALL);
+        }
+    }
+
+    let mut root = split(
+        Rect {
+            x: 0,
+            y: 0,
+            w: LEVEL_SIZE,
+            h: LEVEL_SIZE,
+        },
+        rng,
+    );
+    let mut rooms = Vec::new();
+    place_rooms(&mut root, rng, &mut rooms);
+    if rooms.len() < 4 {
+        return Err(GenerationError::TooFewRooms);
+    }
+
+    for room in rooms.iter() {
+        for x in room.x..room.x + room.w {
+            for y in room.y..room.y + room.h {
+                carve(&mut tile_map, x, y);
+            }
+        }
+    }
+    connect(&root, rng, &mut tile_map);
+
+    let start_room = rooms[rng.gen_range(0, rooms.len())];
+    let start_position = start_room.centre();
+    let exit_room = *rooms
+        .iter()
+        .max_by_key(|r| manhatten_distance(r.centre(), start_position))
+        .unwrap();
+    let exit_position = exit_room.centre();
+    if manhatten_distance(start_position, exit_position) < LEVEL_SIZE / 2 {
+        return Err(GenerationError::ExitTooClose);
+    }
+
+    let mut chode_positions = Vec::new();
+    for room in rooms.iter() {
+        if room.centre() == start_position {
+            continue;
+        }
+        // Bigger rooms get more enemies, keeping them off the edge tiles.
+        let n_enemies = rng.gen_range(0, (room.w * room.h) / 40 + 2);
+        for _ in 0..n_enemies {
+            chode_positions.push((
+                rng.gen_range(room.x + 1, room.x + room.w - 1),
+                rng.gen_range(room.y + 1, room.y + room.h - 1),
+                random_enemy_type(rng),
+            ));
+        }
+    }
+
+    Ok(GeneratedLevel {
+        tile_map,
+        start_position,
+        exit_position,
+        chode_positions,
+        pickup_positions: Vec::new(),
+    })
+}
@@ -1,7 +1,6 @@
-use super::{EnemyType, GeneratedLevel, StringErr, FLOOR, WALL};
-use crate::physics::TileMap;
+use super::{layout_open_level, GeneratedLevel, GenerationError, FLOOR, WALL};
 use crate::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 const LEVEL_SIZE: i32 = 50;
 
@@ -17,8 +16,7 @@ fn r_n(level: &HashMap<(i32, i32), i32>, n: i32, (x, y): (i32, i32)) -> i32 {
 }
 
 #[allow(clippy::cyclomatic_complexity)] // /me cries in professional
-pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
-    let mut rng = rand::thread_rng();
+pub fn try_generate_level<R: Rng>(rng: &mut R) -> Result<GeneratedLevel, GenerationError> {
     let mut level = HashMap::new();
 
     // Initialise randomly
@@ -71,51 +69,15 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
         }
     }
 
-    // TODO: Pick random quadrants for the start/exit.
-    let mut start_position = (-1, -1);
-    'outer: for x in 0..LEVEL_SIZE {
-        for y in 0..=x {
-            if level[&(x, y)] == 0 {
-                start_position = (x, y);
-                break 'outer;
-            }
-        }
-    }
-
-    let mut exit_position = (-1, -1);
-    'outer2: for x in (0..LEVEL_SIZE).rev() {
-        for y in (x..LEVEL_SIZE).rev() {
-            if level[&(x, y)] == 0 {
-                exit_position = (x, y);
-                break 'outer2;
-            }
-        }
-    }
-
-    let mut chode_positions = Vec::new();
-    for _ in 0..30 {
-        let p = (
-            rng.gen_range(0, LEVEL_SIZE),
-            rng.gen_range(0, LEVEL_SIZE),
-            if rng.gen_range(0.0, 1.0) > 0.8 {
-                EnemyType::Shotgunner
-            } else if rng.gen_range(0.0, 1.0) > 0.6 {
-                EnemyType::Spinner
-            } else {
-                EnemyType::Chode
-            },
-        );
-        if level[&(p.0, p.1)] == 0
-            && ((p.0 - start_position.0).abs() >= 10 || (p.1 - start_position.1).abs() >= 10)
-        {
-            chode_positions.push(p);
-        }
-    }
-
-    Ok(GeneratedLevel {
-        tile_map: TileMap { tiles },
-        start_position,
-        exit_position,
-        chode_positions,
-    })
+    let open: HashSet<_> = (0..LEVEL_SIZE)
+        .flat_map(|x| (0..LEVEL_SIZE).map(move |y| (x, y)))
+        .filter(|p| level[p] == 0)
+        .collect();
+    layout_open_level(
+        tiles,
+        &open,
+        (LEVEL_SIZE * LEVEL_SIZE / 4) as usize,
+        LEVEL_SIZE,
+        rng,
+    )
 }
@@ -1,7 +1,15 @@
-use super::{EnemyType, GeneratedLevel, StringErr, FLOOR, WALL};
+use super::room_templates::{self, RoomTemplate, Side, TemplateTile};
+use super::{
+    manhatten_distance, random_enemy_type, GeneratedLevel, GenerationError, FLOOR, PIT, WALL,
+};
 use crate::physics::TileMap;
 use crate::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+const ROOM_SIZE: (i32, i32) = (10, 10);
+// Corridors are carved across these cells of the room's side, so every door has to fall in here.
+const DOOR_SPAN: Range<i32> = 2..8;
 
 // # S-#-#-#
 //   =   o |
@@ -9,7 +17,8 @@ use std::collections::HashSet;
 //     | |
 // # # E-# #
 
-fn carve_room(position: (i32, i32), size: (i32, i32), tile_map: &mut TileMap) {
+fn carve_room(position: (i32, i32), template: &RoomTemplate, tile_map: &mut TileMap) {
+    let size = template.size();
     for x in -1..=size.0 {
         for y in -1..=size.1 {
             tile_map
@@ -17,23 +26,21 @@ fn carve_room(position: (i32, i32), size: (i32, i32), tile_map: &mut TileMap) {
                 .insert((position.0 * 20 + x, position.1 * 20 + y), WALL);
         }
     }
-    for x in 0..size.0 {
-        for y in 0..size.1 {
-            tile_map
-                .tiles
-                .insert((position.0 * 20 + x, position.1 * 20 + y), FLOOR);
-        }
+    for (x, y, tile) in template.tiles() {
+        let tile = match tile {
+            TemplateTile::Wall => WALL,
+            TemplateTile::Pit => PIT,
+            _ => FLOOR,
+        };
+        tile_map
+            .tiles
+            .insert((position.0 * 20 + x, position.1 * 20 + y), tile);
     }
 }
 
-fn manhatten_distance(from: (i32, i32), to: (i32, i32)) -> i32 {
-    (from.0 - to.0).abs() + (from.1 - to.1).abs()
-}
-
 #[allow(clippy::cyclomatic_complexity)] // /me cries in professional
-pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
+pub fn try_generate_level<R: Rng>(rng: &mut R) -> Result<GeneratedLevel, GenerationError> {
     let mut chode_positions = Vec::new();
-    let mut rng = rand::thread_rng();
     let mut tile_map: TileMap = Default::default();
     // Assume start position is always 0, 0
     // Pick an end position
@@ -54,7 +61,7 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
         }
 
         if choices.is_empty() {
-            return Err(StringErr("Got stuck with no choice loop 1".to_string()));
+            return Err(GenerationError::StuckOnMainPath);
         }
         assert!(!choices.is_empty());
 
@@ -81,9 +88,7 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
             }
 
             if choices.is_empty() {
-                return Err(StringErr(
-                    "Got stuck with no choice side loop 1".to_string(),
-                ));
+                return Err(GenerationError::StuckOnSidePath);
             }
             assert!(!choices.is_empty());
 
@@ -118,7 +123,7 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
             }
 
             if choices.is_empty() {
-                return Err(StringErr("Got stuck with no choice loop 2".to_string()));
+                return Err(GenerationError::StuckRejoiningMainPath);
             }
 
             let index = rng.gen_range(0, choices.len());
@@ -128,7 +133,7 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
             visited.insert(current_pos);
         }
         if side_path.len() > 8 {
-            return Err(StringErr("Side path excessively long".to_string()));
+            return Err(GenerationError::SidePathTooLong);
         }
     }
 
@@ -151,7 +156,7 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
         }
 
         if choices.is_empty() {
-            return Err(StringErr("Got stuck with no choice loop 2".to_string()));
+            return Err(GenerationError::StuckReturningToStart);
         }
 
         let index = rng.gen_range(0, choices.len());
@@ -161,46 +166,77 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
         visited.insert(current_pos);
     }
     if path.len() >= 10 {
-        return Err(StringErr("Initial path excessively long".to_string()));
+        return Err(GenerationError::MainPathTooLong);
     }
     path.push(start);
 
-    for room in path.iter() {
-        carve_room(*room, (10, 10), &mut tile_map);
-        if *room == (0, 0) {
-            continue;
+    // Work out which sides of each room the corridors join so the templates can line up doors.
+    let mut doors: HashMap<(i32, i32), Vec<Side>> = HashMap::new();
+    for route in [&path, &side_path].iter() {
+        for pair in route.windows(2) {
+            let direction = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+            doors
+                .entry(pair[0])
+                .or_default()
+                .push(Side::from_direction(direction));
+            doors
+                .entry(pair[1])
+                .or_default()
+                .push(Side::from_direction((-direction.0, -direction.1)));
         }
-        let n_enemies = rng.gen_range(0, 5);
-        for _ in 0..n_enemies {
-            let pos = (rng.gen_range(2, 8), rng.gen_range(2, 8));
-            chode_positions.push((
-                room.0 * 20 + pos.0,
-                room.1 * 20 + pos.1,
-                if rng.gen_range(0.0, 1.0) > 0.8 {
-                    EnemyType::Shotgunner
-                } else if rng.gen_range(0.0, 1.0) > 0.6 {
-                    EnemyType::Spinner
-                } else {
-                    EnemyType::Chode
-                },
-            ));
+    }
+    let mut rooms = Vec::new();
+    for room in path.iter().chain(side_path.iter()) {
+        if !rooms.contains(room) {
+            rooms.push(*room);
         }
     }
-    for room in side_path.iter() {
-        carve_room(*room, (10, 10), &mut tile_map);
-        let n_enemies = rng.gen_range(0, 5);
-        for _ in 0..n_enemies {
-            let pos = (rng.gen_range(2, 8), rng.gen_range(2, 8));
+
+    let templates = room_templates::templates();
+    let mut pickup_positions = Vec::new();
+    for room in rooms.iter() {
+        // The start and exit are in the middle of their rooms so those need to be clear.
+        let tag = if *room == start || *room == end {
+            "open"
+        } else {
+            "combat"
+        };
+        let template = room_templates::choose_template(
+            &templates,
+            ROOM_SIZE,
+            tag,
+            &doors[room],
+            DOOR_SPAN,
+            rng,
+        )
+        .ok_or(GenerationError::NoRoomTemplate)?;
+        carve_room(*room, &template, &mut tile_map);
+
+        let mut enemy_markers = Vec::new();
+        for (x, y, tile) in template.tiles() {
+            match tile {
+                TemplateTile::Enemy => enemy_markers.push((x, y)),
+                TemplateTile::Pickup => pickup_positions.push((room.0 * 20 + x, room.1 * 20 + y)),
+                _ => {}
+            }
+        }
+        if *room == start {
+            continue;
+        }
+        if enemy_markers.is_empty() {
+            let n_enemies = rng.gen_range(0, 5);
+            for _ in 0..n_enemies {
+                let pos = (rng.gen_range(2, 8), rng.gen_range(2, 8));
+                if template.tile(pos.0, pos.1).walkable() {
+                    enemy_markers.push(pos);
+                }
+            }
+        }
+        for pos in enemy_markers {
             chode_positions.push((
                 room.0 * 20 + pos.0,
                 room.1 * 20 + pos.1,
-                if rng.gen_range(0.0, 1.0) > 0.8 {
-                    EnemyType::Shotgunner
-                } else if rng.gen_range(0.0, 1.0) > 0.6 {
-                    EnemyType::Spinner
-                } else {
-                    EnemyType::Chode
-                },
+                random_enemy_type(rng),
             ));
         }
     }
@@ -313,5 +349,6 @@ pub fn try_generate_level() -> Result<GeneratedLevel, StringErr> {
         start_position: (5, 5),
         exit_position,
         chode_positions,
+        pickup_positions,
     })
 }
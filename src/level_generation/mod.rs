@@ -1,57 +1,347 @@
 use crate::physics::{Tile, TileMap};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 const WALL: Tile = Tile {
     collision: true,
+    pit: false,
     colour: rgba!(128, 128, 128, 1.0),
 };
 
 const FLOOR: Tile = Tile {
     collision: false,
+    pit: false,
     colour: rgba!(223, 201, 96, 1.0),
 };
 
+const TEMPLE_FLOOR: Tile = Tile {
+    collision: false,
+    pit: false,
+    colour: rgba!(178, 150, 110, 1.0),
+};
+
+const PIT: Tile = Tile {
+    collision: true,
+    pit: true,
+    colour: rgba!(48, 32, 16, 1.0),
+};
+
 pub const BOSS_ARENA_SIZE_X: i32 = 10;
 pub const BOSS_ARENA_SIZE_Y: i32 = 8;
 
-#[derive(Copy, Clone, Debug)]
+// Give up on a style after this many failed attempts and use the fallback layout instead, so a
+// generator bug can't hang the game.
+const MAX_ATTEMPTS: u32 = 50;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize)]
 pub enum LevelStyle {
     Cyclic,
     CellularAutomata,
+    Bsp,
+    WaveFunctionCollapse,
+    // A single room full of enemies, for ambushes on the world map.
+    Arena,
+}
+
+impl LevelStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            LevelStyle::Cyclic => "Catacombs",
+            LevelStyle::CellularAutomata => "Caverns",
+            LevelStyle::Bsp => "Tomb",
+            LevelStyle::WaveFunctionCollapse => "Temple",
+            LevelStyle::Arena => "Arena",
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct StyleMetrics {
+    pub attempts: u32,
+    pub failures: HashMap<GenerationError, u32>,
+    pub fallbacks: u32,
+}
+
+#[derive(Default, Debug)]
+pub struct GenerationMetrics {
+    pub styles: HashMap<LevelStyle, StyleMetrics>,
+    // How the most recent level went.
+    pub last_failed_attempts: u32,
+    pub last_used_fallback: bool,
+}
+
+pub fn generate_level(style: LevelStyle, metrics: &mut GenerationMetrics) -> GeneratedLevel {
+    generate_level_with_rng(style, &mut rand::thread_rng(), metrics)
 }
 
-pub fn generate_level(style: LevelStyle) -> GeneratedLevel {
-    let gen_fn = match style {
+pub fn generate_level_with_rng<R: Rng>(
+    style: LevelStyle,
+    rng: &mut R,
+    metrics: &mut GenerationMetrics,
+) -> GeneratedLevel {
+    let gen_fn: fn(&mut R) -> Result<GeneratedLevel, GenerationError> = match style {
         LevelStyle::Cyclic => cyclic::try_generate_level,
         LevelStyle::CellularAutomata => cellular_automata::try_generate_level,
+        LevelStyle::Bsp => bsp::try_generate_level,
+        LevelStyle::WaveFunctionCollapse => wave_function_collapse::try_generate_level,
+        LevelStyle::Arena => ambush_arena,
     };
-    loop {
-        match gen_fn() {
+    metrics.last_failed_attempts = 0;
+    metrics.last_used_fallback = false;
+    let style_metrics = metrics.styles.entry(style).or_default();
+    for _ in 0..MAX_ATTEMPTS {
+        style_metrics.attempts += 1;
+        match gen_fn(rng) {
             Ok(l) => return l,
             Err(e) => {
-                dbg!(e);
+                *style_metrics.failures.entry(e).or_insert(0) += 1;
+                metrics.last_failed_attempts += 1;
             }
         }
     }
+    style_metrics.fallbacks += 1;
+    metrics.last_used_fallback = true;
+    fallback_level()
 }
 
+// A plain hall with the exit at the far end. Boring, but it can't fail.
+fn fallback_level() -> GeneratedLevel {
+    let (w, h) = (40, 8);
+    let mut tile_map: TileMap = Default::default();
+    for x in -1..=w {
+        for y in -1..=h {
+            tile_map.tiles.insert((x, y), WALL);
+        }
+    }
+    for x in 0..w {
+        for y in 0..h {
+            tile_map.tiles.insert((x, y), FLOOR);
+        }
+    }
+    GeneratedLevel {
+        tile_map,
+        start_position: (2, h / 2),
+        exit_position: (w - 3, h / 2),
+        chode_positions: vec![
+            (15, 2, EnemyType::Chode),
+            (20, 5, EnemyType::Spinner),
+            (30, 3, EnemyType::Shotgunner),
+        ],
+        pickup_positions: Vec::new(),
+    }
+}
+
+const AMBUSH_ARENA_SIZE: (i32, i32) = (21, 15);
+const AMBUSHERS: (usize, usize) = (4, 7);
+
+// Enemies spread across the top of an open room, with the player entering at the bottom and the
+// way out behind them.
+fn ambush_arena<R: Rng>(rng: &mut R) -> Result<GeneratedLevel, GenerationError> {
+    let (w, h) = AMBUSH_ARENA_SIZE;
+    let mut tile_map: TileMap = Default::default();
+    for x in -1..=w {
+        for y in -1..=h {
+            tile_map.tiles.insert((x, y), WALL);
+        }
+    }
+    for x in 0..w {
+        for y in 0..h {
+            tile_map.tiles.insert((x, y), FLOOR);
+        }
+    }
+    let mut chode_positions = Vec::new();
+    for _ in 0..rng.gen_range(AMBUSHERS.0, AMBUSHERS.1) {
+        chode_positions.push((
+            rng.gen_range(1, w - 1),
+            rng.gen_range(2, h / 2 + 2),
+            random_enemy_type(rng),
+        ));
+    }
+    Ok(GeneratedLevel {
+        tile_map,
+        start_position: (w / 2, h - 2),
+        exit_position: (w / 2, 0),
+        chode_positions,
+        pickup_positions: Vec::new(),
+    })
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum EnemyType {
     Chode,
     Shotgunner,
     Spinner,
 }
 
+fn random_enemy_type(rng: &mut impl Rng) -> EnemyType {
+    if rng.gen_range(0.0, 1.0) > 0.8 {
+        EnemyType::Shotgunner
+    } else if rng.gen_range(0.0, 1.0) > 0.6 {
+        EnemyType::Spinner
+    } else {
+        EnemyType::Chode
+    }
+}
+
+fn manhatten_distance(from: (i32, i32), to: (i32, i32)) -> i32 {
+    (from.0 - to.0).abs() + (from.1 - to.1).abs()
+}
+
+const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// Splits open tiles into 4-connected regions.
+fn find_regions(open: &HashSet<(i32, i32)>) -> Vec<HashSet<(i32, i32)>> {
+    let mut regions = Vec::new();
+    let mut seen = HashSet::new();
+    for &tile in open.iter() {
+        if seen.contains(&tile) {
+            continue;
+        }
+        let region: HashSet<_> = path_distances(open, tile).keys().cloned().collect();
+        seen.extend(region.iter().cloned());
+        regions.push(region);
+    }
+    regions
+}
+
+// Walking distance from `from` to every open tile reachable from it.
+fn path_distances(open: &HashSet<(i32, i32)>, from: (i32, i32)) -> HashMap<(i32, i32), i32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    distances.insert(from, 0);
+    queue.push_back(from);
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[&current];
+        for d in DIRECTIONS.iter() {
+            let next = (current.0 + d.0, current.1 + d.1);
+            if open.contains(&next) && !distances.contains_key(&next) {
+                distances.insert(next, distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+// Two tiles roughly as far apart as possible by walking distance, found with a double sweep:
+// the furthest tile from anywhere is one end, and the furthest tile from that is the other.
+fn furthest_pair(region: &HashSet<(i32, i32)>) -> ((i32, i32), (i32, i32), i32) {
+    let any = *region.iter().min().expect("Region should not be empty.");
+    let furthest = |from| {
+        path_distances(region, from)
+            .into_iter()
+            .max_by_key(|&(tile, distance)| (distance, tile))
+            .expect("Distances always include the starting tile.")
+    };
+    let (a, _) = furthest(any);
+    let (b, distance) = furthest(a);
+    (a, b, distance)
+}
+
+const ENEMY_SPAWN_ATTEMPTS: u32 = 30;
+const MIN_ENEMY_DISTANCE: i32 = 10;
+
+// For the free-form styles (caves, ruins): only keep the largest connected area so nothing (including
+// the exit) ends up sealed off, put the start and exit as far apart as possible and scatter enemies
+// around, away from the start.
+fn layout_open_level<R: Rng>(
+    mut tiles: HashMap<(i32, i32), Tile>,
+    open: &HashSet<(i32, i32)>,
+    min_region_size: usize,
+    min_path_length: i32,
+    rng: &mut R,
+) -> Result<GeneratedLevel, GenerationError> {
+    let region = find_regions(open)
+        .into_iter()
+        .max_by_key(|r| (r.len(), r.iter().min().cloned()))
+        .ok_or(GenerationError::NoOpenTiles)?;
+    if region.len() < min_region_size {
+        return Err(GenerationError::RegionTooSmall);
+    }
+    for p in open.difference(&region) {
+        tiles.insert(*p, WALL);
+    }
+
+    let (start_position, exit_position, path_length) = furthest_pair(&region);
+    if path_length < min_path_length {
+        return Err(GenerationError::ExitTooClose);
+    }
+
+    let distance_from_start = path_distances(&region, start_position);
+    let mut region_tiles: Vec<_> = region.iter().cloned().collect();
+    region_tiles.sort();
+    let mut chode_positions = Vec::new();
+    for _ in 0..ENEMY_SPAWN_ATTEMPTS {
+        let p = region_tiles[rng.gen_range(0, region_tiles.len())];
+        if distance_from_start[&p] >= MIN_ENEMY_DISTANCE {
+            chode_positions.push((p.0, p.1, random_enemy_type(rng)));
+        }
+    }
+
+    Ok(GeneratedLevel {
+        tile_map: TileMap { tiles },
+        start_position,
+        exit_position,
+        chode_positions,
+        pickup_positions: Vec::new(),
+    })
+}
+
+// Walking distance from the start to the exit, if they're connected at all.
+pub fn path_length(level: &GeneratedLevel) -> Option<i32> {
+    let open: HashSet<_> = level
+        .tile_map
+        .tiles
+        .iter()
+        .filter(|(_, tile)| !tile.collision)
+        .map(|(position, _)| *position)
+        .collect();
+    path_distances(&open, level.start_position)
+        .get(&level.exit_position)
+        .cloned()
+}
+
 pub struct GeneratedLevel {
     pub tile_map: TileMap,
     pub start_position: (i32, i32),
     pub exit_position: (i32, i32),
     pub chode_positions: Vec<(i32, i32, EnemyType)>,
+    pub pickup_positions: Vec<(i32, i32)>,
 }
 
-#[derive(Debug)]
-pub struct StringErr(String);
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum GenerationError {
+    // Cyclic
+    StuckOnMainPath,
+    StuckOnSidePath,
+    StuckRejoiningMainPath,
+    StuckReturningToStart,
+    SidePathTooLong,
+    MainPathTooLong,
+    NoRoomTemplate,
+    // BSP
+    TooFewRooms,
+    // Wave function collapse
+    Contradiction,
+    // Shared
+    NoOpenTiles,
+    RegionTooSmall,
+    ExitTooClose,
+}
 
+mod bsp;
 mod cellular_automata;
 mod cyclic;
+mod room_templates;
+mod wave_function_collapse;
+
+// Parses and checks the room templates and WFC sample up front, so a bad file stops the game when
+// it starts rather than partway through a run.
+pub fn load_data() {
+    room_templates::templates();
+    wave_function_collapse::rules();
+}
 
 pub fn make_boss_arena() -> TileMap {
     let mut out: TileMap = Default::default();
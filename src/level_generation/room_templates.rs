@@ -0,0 +1,209 @@
+use crate::prelude::*;
+use serde::Deserialize;
+use std::ops::Range;
+use std::rc::Rc;
+
+// Room templates are authored as ASCII art inside RON files under rooms/.
+//   # wall      . floor     D door (floor where a corridor may join)
+//   e enemy     p pickup    ^ pit
+const TEMPLATE_SOURCES: &[(&str, &str)] = &[
+    ("rooms/open.ron", include_str!("../../rooms/open.ron")),
+    ("rooms/pillars.ron", include_str!("../../rooms/pillars.ron")),
+    ("rooms/cross.ron", include_str!("../../rooms/cross.ron")),
+    (
+        "rooms/treasure.ron",
+        include_str!("../../rooms/treasure.ron"),
+    ),
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TemplateTile {
+    Wall,
+    Floor,
+    Door,
+    Enemy,
+    Pickup,
+    Pit,
+}
+
+impl TemplateTile {
+    fn from_char(c: char) -> Option<TemplateTile> {
+        match c {
+            '#' => Some(TemplateTile::Wall),
+            '.' => Some(TemplateTile::Floor),
+            'D' => Some(TemplateTile::Door),
+            'e' => Some(TemplateTile::Enemy),
+            'p' => Some(TemplateTile::Pickup),
+            '^' => Some(TemplateTile::Pit),
+            _ => None,
+        }
+    }
+
+    pub fn walkable(self) -> bool {
+        match self {
+            TemplateTile::Wall | TemplateTile::Pit => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Side {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Side {
+    pub fn from_direction(direction: (i32, i32)) -> Side {
+        match direction {
+            (1, 0) => Side::East,
+            (-1, 0) => Side::West,
+            (0, -1) => Side::North,
+            (0, 1) => Side::South,
+            _ => panic!("Rooms only connect orthogonally, got {:?}", direction),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoomTemplate {
+    pub name: String,
+    pub tags: Vec<String>,
+    layout: Vec<String>,
+}
+
+impl RoomTemplate {
+    pub fn size(&self) -> (i32, i32) {
+        (
+            self.layout[0].chars().count() as i32,
+            self.layout.len() as i32,
+        )
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> TemplateTile {
+        let c = self.layout[y as usize]
+            .chars()
+            .nth(x as usize)
+            .expect("Template layouts are validated when loaded.");
+        TemplateTile::from_char(c).expect("Template layouts are validated when loaded.")
+    }
+
+    pub fn tiles<'a>(&'a self) -> impl Iterator<Item = (i32, i32, TemplateTile)> + 'a {
+        let (w, h) = self.size();
+        (0..h).flat_map(move |y| (0..w).map(move |x| (x, y, self.tile(x, y))))
+    }
+
+    // Quarter turn clockwise.
+    fn rotated(&self) -> RoomTemplate {
+        let (w, h) = self.size();
+        let layout = (0..w)
+            .map(|y| {
+                (0..h)
+                    .map(|x| {
+                        self.layout[(h - 1 - x) as usize]
+                            .chars()
+                            .nth(y as usize)
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        RoomTemplate {
+            layout,
+            ..self.clone()
+        }
+    }
+
+    // Whether a corridor arriving on `side` across the cells in `span` meets at least one door.
+    fn has_door(&self, side: Side, span: Range<i32>) -> bool {
+        let (w, h) = self.size();
+        span.into_iter().any(|i| {
+            let (x, y) = match side {
+                Side::North => (i, 0),
+                Side::South => (i, h - 1),
+                Side::West => (0, i),
+                Side::East => (w - 1, i),
+            };
+            x >= 0 && x < w && y >= 0 && y < h && self.tile(x, y) == TemplateTile::Door
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.layout.is_empty() {
+            return Err("layout is empty".to_string());
+        }
+        let (w, h) = self.size();
+        for (y, row) in self.layout.iter().enumerate() {
+            if row.chars().count() as i32 != w {
+                return Err(format!("row {} is not {} tiles wide", y, w));
+            }
+            for (x, c) in row.chars().enumerate() {
+                let tile = TemplateTile::from_char(c)
+                    .ok_or_else(|| format!("unknown tile '{}' at ({}, {})", c, x, y))?;
+                let (x, y) = (x as i32, y as i32);
+                let on_edge = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+                if tile == TemplateTile::Door && !on_edge {
+                    return Err(format!("door at ({}, {}) is not on the edge", x, y));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_templates() -> Vec<RoomTemplate> {
+    TEMPLATE_SOURCES
+        .iter()
+        .map(|(file, source)| {
+            let template: RoomTemplate = ron::de::from_str(source)
+                .unwrap_or_else(|e| panic!("Bad room template {}:{}", file, e));
+            if let Err(e) = template.validate() {
+                panic!("Bad room template {} ({}): {}", file, template.name, e);
+            }
+            template
+        })
+        .collect()
+}
+
+thread_local! {
+    // Parsed on first use rather than for every generation attempt.
+    static TEMPLATES: Rc<Vec<RoomTemplate>> = Rc::new(load_templates());
+}
+
+pub fn templates() -> Rc<Vec<RoomTemplate>> {
+    TEMPLATES.with(Rc::clone)
+}
+
+// Picks a random template (in any rotation) of the given size and tag that has doors where the
+// corridors in `doors` will join it.
+pub fn choose_template(
+    templates: &[RoomTemplate],
+    size: (i32, i32),
+    tag: &str,
+    doors: &[Side],
+    door_span: Range<i32>,
+    rng: &mut impl Rng,
+) -> Option<RoomTemplate> {
+    let mut candidates = Vec::new();
+    for template in templates.iter().filter(|t| t.tags.iter().any(|t| t == tag)) {
+        let mut rotation = template.clone();
+        for _ in 0..4 {
+            if rotation.size() == size
+                && doors
+                    .iter()
+                    .all(|side| rotation.has_door(*side, door_span.clone()))
+            {
+                candidates.push(rotation.clone());
+            }
+            rotation = rotation.rotated();
+        }
+    }
+    if candidates.is_empty() {
+        None
+    } else {
+        let index = rng.gen_range(0, candidates.len());
+        Some(candidates.swap_remove(index))
+    }
+}
@@ -0,0 +1,240 @@
+use super::{
+    layout_open_level, GeneratedLevel, GenerationError, DIRECTIONS, FLOOR, PIT, TEMPLE_FLOOR, WALL,
+};
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+// Simple tiled model: which tiles can sit next to which (and how common each is) is learnt from a
+// small hand drawn sample, then the level is filled in one cell at a time keeping to those rules.
+//   # wall      . sand      + flagstones      ^ pit
+const SAMPLE_SOURCE: (&str, &str) = ("samples/ruins.txt", include_str!("../../samples/ruins.txt"));
+
+const LEVEL_SIZE: i32 = 40;
+const MAX_BACKTRACKS: u32 = 200;
+// How many decisions back we can undo when we hit a contradiction.
+const MAX_SNAPSHOTS: usize = 64;
+
+pub struct Rules {
+    tiles: Vec<char>,
+    weights: Vec<u32>,
+    // allowed[t][d] is the set of tiles that can be in direction DIRECTIONS[d] from tile t.
+    allowed: Vec<[u64; 4]>,
+}
+
+impl Rules {
+    fn learn(sample: &str) -> Result<Rules, String> {
+        let rows: Vec<Vec<char>> = sample
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.chars().collect())
+            .collect();
+        let mut tiles: Vec<char> = Vec::new();
+        let mut weights = Vec::new();
+        for c in rows.iter().flatten() {
+            match tiles.iter().position(|t| t == c) {
+                Some(i) => weights[i] += 1,
+                None => {
+                    tiles.push(*c);
+                    weights.push(1);
+                }
+            }
+        }
+        if tiles.len() > 64 {
+            return Err(format!(
+                "{} kinds of tile, at most 64 are allowed",
+                tiles.len()
+            ));
+        }
+        // The level is sealed in with walls.
+        if !tiles.contains(&'#') {
+            return Err("no wall tile '#'".to_string());
+        }
+
+        let index = |c: char| tiles.iter().position(|t| *t == c).unwrap();
+        let mut allowed = vec![[0u64; 4]; tiles.len()];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                for (d, dir) in DIRECTIONS.iter().enumerate() {
+                    let (nx, ny) = (x as i32 + dir.0, y as i32 + dir.1);
+                    if let Some(n) = rows
+                        .get(ny as usize)
+                        .and_then(|r| r.get(nx as usize))
+                        .filter(|_| nx >= 0 && ny >= 0)
+                    {
+                        allowed[index(*c)][d] |= 1 << index(*n);
+                    }
+                }
+            }
+        }
+        Ok(Rules {
+            tiles,
+            weights,
+            allowed,
+        })
+    }
+
+    fn mask_of(&self, c: char) -> u64 {
+        1 << self
+            .tiles
+            .iter()
+            .position(|t| *t == c)
+            .expect("WFC samples are validated when loaded.")
+    }
+}
+
+thread_local! {
+    // Learnt on first use rather than for every generation attempt.
+    static RULES: Rc<Rules> = {
+        let (file, source) = SAMPLE_SOURCE;
+        Rc::new(Rules::learn(source).unwrap_or_else(|e| panic!("Bad WFC sample {}: {}", file, e)))
+    };
+}
+
+pub fn rules() -> Rc<Rules> {
+    RULES.with(Rc::clone)
+}
+
+fn index(x: i32, y: i32) -> usize {
+    (y * LEVEL_SIZE + x) as usize
+}
+
+// Removes options that are no longer allowed next to the cells in `pending`, spreading outwards.
+// Returns false if some cell is left with no options at all.
+fn propagate(cells: &mut [u64], rules: &Rules, mut pending: Vec<usize>) -> bool {
+    while let Some(i) = pending.pop() {
+        let (x, y) = (i as i32 % LEVEL_SIZE, i as i32 / LEVEL_SIZE);
+        for (d, dir) in DIRECTIONS.iter().enumerate() {
+            let (nx, ny) = (x + dir.0, y + dir.1);
+            if nx < 0 || ny < 0 || nx >= LEVEL_SIZE || ny >= LEVEL_SIZE {
+                continue;
+            }
+            let mut allowed = 0;
+            for t in 0..rules.tiles.len() {
+                if cells[i] & (1 << t) != 0 {
+                    allowed |= rules.allowed[t][d];
+                }
+            }
+            let n = index(nx, ny);
+            let reduced = cells[n] & allowed;
+            if reduced == 0 {
+                return false;
+            }
+            if reduced != cells[n] {
+                cells[n] = reduced;
+                pending.push(n);
+            }
+        }
+    }
+    true
+}
+
+fn collapse<R: Rng>(rules: &Rules, rng: &mut R) -> Result<Vec<u64>, GenerationError> {
+    let all = if rules.tiles.len() == 64 {
+        !0
+    } else {
+        (1u64 << rules.tiles.len()) - 1
+    };
+    let mut cells = vec![all; (LEVEL_SIZE * LEVEL_SIZE) as usize];
+
+    // Seal the level in.
+    let wall = rules.mask_of('#');
+    let mut border = Vec::new();
+    for i in 0..LEVEL_SIZE {
+        for &(x, y) in [(i, 0), (i, LEVEL_SIZE - 1), (0, i), (LEVEL_SIZE - 1, i)].iter() {
+            cells[index(x, y)] = wall;
+            border.push(index(x, y));
+        }
+    }
+    if !propagate(&mut cells, rules, border) {
+        return Err(GenerationError::Contradiction);
+    }
+
+    let mut snapshots = VecDeque::new();
+    let mut backtracks = 0;
+    loop {
+        // Collapse whichever undecided cell has the fewest options left.
+        let fewest = cells
+            .iter()
+            .map(|c| c.count_ones())
+            .filter(|n| *n > 1)
+            .min();
+        let fewest = match fewest {
+            Some(n) => n,
+            None => return Ok(cells),
+        };
+        let candidates: Vec<_> = (0..cells.len())
+            .filter(|i| cells[*i].count_ones() == fewest)
+            .collect();
+        let cell = candidates[rng.gen_range(0, candidates.len())];
+
+        let options: Vec<_> = (0..rules.tiles.len())
+            .filter(|t| cells[cell] & (1 << t) != 0)
+            .collect();
+        let total: u32 = options.iter().map(|t| rules.weights[*t]).sum();
+        let mut roll = rng.gen_range(0, total);
+        let mut choice = options[0];
+        for t in options {
+            if roll < rules.weights[t] {
+                choice = t;
+                break;
+            }
+            roll -= rules.weights[t];
+        }
+
+        snapshots.push_back((cells.clone(), cell, 1u64 << choice));
+        if snapshots.len() > MAX_SNAPSHOTS {
+            snapshots.pop_front();
+        }
+        cells[cell] = 1 << choice;
+        let mut ok = propagate(&mut cells, rules, vec![cell]);
+        // On a contradiction go back to before the last decision and rule out what we picked. If
+        // that contradicts too, keep going back further.
+        while !ok {
+            backtracks += 1;
+            if backtracks > MAX_BACKTRACKS {
+                return Err(GenerationError::Contradiction);
+            }
+            let (saved, cell, picked) =
+                snapshots.pop_back().ok_or(GenerationError::Contradiction)?;
+            cells = saved;
+            cells[cell] &= !picked;
+            ok = cells[cell] != 0 && propagate(&mut cells, rules, vec![cell]);
+        }
+    }
+}
+
+pub fn try_generate_level<R: Rng>(rng: &mut R) -> Result<GeneratedLevel, GenerationError> {
+    let rules = rules();
+    let cells = collapse(&rules, rng)?;
+
+    let mut tiles = HashMap::new();
+    for x in -1..=LEVEL_SIZE {
+        for y in -1..=LEVEL_SIZE {
+            tiles.insert((x, y), WALL);
+        }
+    }
+    let mut open = HashSet::new();
+    for x in 0..LEVEL_SIZE {
+        for y in 0..LEVEL_SIZE {
+            let tile = match rules.tiles[cells[index(x, y)].trailing_zeros() as usize] {
+                '#' => WALL,
+                '+' => TEMPLE_FLOOR,
+                '^' => PIT,
+                _ => FLOOR,
+            };
+            if !tile.collision {
+                open.insert((x, y));
+            }
+            tiles.insert((x, y), tile);
+        }
+    }
+
+    layout_open_level(
+        tiles,
+        &open,
+        (LEVEL_SIZE * LEVEL_SIZE / 4) as usize,
+        LEVEL_SIZE,
+        rng,
+    )
+}
@@ -0,0 +1,274 @@
+// Command line tool for looking at generated levels without playing through them:
+//   specs-test levelgen <cyclic|cellular|bsp|wfc|arena> [seed] [count] [out_dir]
+// Writes an ASCII dump and a PNG of each level to out_dir and prints some stats.
+
+use crate::level_generation::{self, EnemyType, GeneratedLevel, GenerationMetrics, LevelStyle};
+use quicksilver::graphics::Color;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+const PIXELS_PER_TILE: i32 = 4;
+
+fn parse_style(name: &str) -> Option<LevelStyle> {
+    match name {
+        "cyclic" => Some(LevelStyle::Cyclic),
+        "cellular" => Some(LevelStyle::CellularAutomata),
+        "bsp" => Some(LevelStyle::Bsp),
+        "wfc" => Some(LevelStyle::WaveFunctionCollapse),
+        "arena" => Some(LevelStyle::Arena),
+        _ => None,
+    }
+}
+
+pub fn run(args: &[String]) {
+    let style = match args.get(0).and_then(|s| parse_style(s)) {
+        Some(style) => style,
+        None => {
+            eprintln!("Usage: levelgen <cyclic|cellular|bsp|wfc|arena> [seed] [count] [out_dir]");
+            return;
+        }
+    };
+    let seed = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(0u64);
+    let count = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(1u64);
+    let out_dir = Path::new(args.get(3).map(String::as_str).unwrap_or("levelgen"));
+    std::fs::create_dir_all(out_dir).expect("Couldn't create output directory");
+
+    let mut metrics = GenerationMetrics::default();
+    for seed in seed..seed + count {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let level = level_generation::generate_level_with_rng(style, &mut rng, &mut metrics);
+        let grid = Grid::new(&level);
+
+        let name = format!("{:?}_{}", style, seed);
+        std::fs::write(out_dir.join(format!("{}.txt", name)), grid.ascii())
+            .expect("Couldn't write ASCII dump");
+        grid.write_png(&out_dir.join(format!("{}.png", name)))
+            .expect("Couldn't write PNG");
+
+        let path_length = level_generation::path_length(&level)
+            .map(|l| l.to_string())
+            .unwrap_or_else(|| "unreachable".to_string());
+        println!(
+            "{}: retries {}{}, floor ratio {:.2}, enemies {}, path length {}",
+            name,
+            metrics.last_failed_attempts,
+            if metrics.last_used_fallback {
+                " (used fallback)"
+            } else {
+                ""
+            },
+            grid.floor_ratio(),
+            level.chode_positions.len(),
+            path_length,
+        );
+    }
+    println!("{:#?}", metrics);
+}
+
+#[derive(Copy, Clone)]
+enum Cell {
+    Void,
+    Wall,
+    Floor,
+    Pit,
+    Start,
+    Exit,
+    Enemy(EnemyType),
+    Pickup,
+}
+
+impl Cell {
+    fn ascii(self) -> char {
+        match self {
+            Cell::Void => ' ',
+            Cell::Wall => '#',
+            Cell::Floor => '.',
+            Cell::Pit => '^',
+            Cell::Start => 'S',
+            Cell::Exit => 'E',
+            Cell::Enemy(EnemyType::Chode) => 'c',
+            Cell::Enemy(EnemyType::Shotgunner) => 'g',
+            Cell::Enemy(EnemyType::Spinner) => 's',
+            Cell::Pickup => 'p',
+        }
+    }
+
+    fn colour(self, tile_colour: Color) -> Color {
+        match self {
+            Cell::Void => Color::BLACK,
+            Cell::Wall | Cell::Floor | Cell::Pit => tile_colour,
+            Cell::Start => Color::BLUE,
+            Cell::Exit => rgba!(0, 128, 128, 1.0),
+            Cell::Enemy(EnemyType::Spinner) => Color::ORANGE,
+            Cell::Enemy(_) => Color::RED,
+            Cell::Pickup => rgba!(255, 105, 180, 1.0),
+        }
+    }
+}
+
+struct Grid {
+    min: (i32, i32),
+    size: (i32, i32),
+    cells: HashMap<(i32, i32), (Cell, Color)>,
+}
+
+impl Grid {
+    fn new(level: &GeneratedLevel) -> Grid {
+        let tiles = &level.tile_map.tiles;
+        let min_x = tiles.keys().map(|p| p.0).min().unwrap_or(0);
+        let min_y = tiles.keys().map(|p| p.1).min().unwrap_or(0);
+        let max_x = tiles.keys().map(|p| p.0).max().unwrap_or(0);
+        let max_y = tiles.keys().map(|p| p.1).max().unwrap_or(0);
+
+        let mut cells = HashMap::new();
+        for (position, tile) in tiles.iter() {
+            let cell = if tile.pit {
+                Cell::Pit
+            } else if tile.collision {
+                Cell::Wall
+            } else {
+                Cell::Floor
+            };
+            cells.insert(*position, (cell, tile.colour));
+        }
+        let mut mark = |position: (i32, i32), cell: Cell| {
+            let colour = cells.get(&position).map(|c| c.1).unwrap_or(Color::BLACK);
+            cells.insert(position, (cell, colour));
+        };
+        for &(x, y, enemy_type) in level.chode_positions.iter() {
+            mark((x, y), Cell::Enemy(enemy_type));
+        }
+        for &position in level.pickup_positions.iter() {
+            mark(position, Cell::Pickup);
+        }
+        mark(level.start_position, Cell::Start);
+        mark(level.exit_position, Cell::Exit);
+
+        Grid {
+            min: (min_x, min_y),
+            size: (max_x - min_x + 1, max_y - min_y + 1),
+            cells,
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> (Cell, Color) {
+        self.cells
+            .get(&(self.min.0 + x, self.min.1 + y))
+            .cloned()
+            .unwrap_or((Cell::Void, Color::BLACK))
+    }
+
+    fn ascii(&self) -> String {
+        let mut out = String::new();
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                out.push(self.get(x, y).0.ascii());
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn floor_ratio(&self) -> f32 {
+        let walkable = self
+            .cells
+            .values()
+            .filter(|(cell, _)| match cell {
+                Cell::Void | Cell::Wall | Cell::Pit => false,
+                _ => true,
+            })
+            .count();
+        walkable as f32 / (self.size.0 * self.size.1) as f32
+    }
+
+    fn write_png(&self, path: &Path) -> io::Result<()> {
+        let width = self.size.0 * PIXELS_PER_TILE;
+        let height = self.size.1 * PIXELS_PER_TILE;
+        let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+        for py in 0..height {
+            for px in 0..width {
+                let (cell, tile_colour) = self.get(px / PIXELS_PER_TILE, py / PIXELS_PER_TILE);
+                let colour = cell.colour(tile_colour);
+                rgb.push((colour.r * 255.0) as u8);
+                rgb.push((colour.g * 255.0) as u8);
+                rgb.push((colour.b * 255.0) as u8);
+            }
+        }
+        png::write_rgb(&mut File::create(path)?, width as u32, height as u32, &rgb)
+    }
+}
+
+// Just enough of PNG to dump an RGB image: one IDAT of uncompressed deflate blocks.
+mod png {
+    use std::io::{self, Write};
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = 0xffff_ffffu32;
+        for &b in bytes {
+            crc ^= u32::from(b);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    fn adler32(bytes: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in bytes {
+            a = (a + u32::from(byte)) % 65521;
+            b = (b + a) % 65521;
+        }
+        (b << 16) | a
+    }
+
+    fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        let mut crc_input = kind.to_vec();
+        crc_input.extend_from_slice(data);
+        out.write_all(&crc_input)?;
+        out.write_all(&crc32(&crc_input).to_be_bytes())
+    }
+
+    pub fn write_rgb(out: &mut impl Write, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8 bit RGB, default compression/filter, no interlacing.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(out, b"IHDR", &header)?;
+
+        // Each scanline is prefixed with filter type 0 (none).
+        let stride = width as usize * 3;
+        let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+        for row in rgb.chunks(stride) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<_> = raw.chunks(0xffff).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            let last = i == blocks.len() - 1;
+            zlib.push(if last { 1 } else { 0 });
+            let len = block.len() as u16;
+            zlib.extend_from_slice(&len.to_le_bytes());
+            zlib.extend_from_slice(&(!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+        write_chunk(out, b"IDAT", &zlib)?;
+
+        write_chunk(out, b"IEND", &[])
+    }
+}
@@ -28,25 +28,45 @@ use player::{PlayerControlSystem, PlayerDeath};
 
 mod gameplay;
 use gameplay::{
-    BossDeathSystem, BulletSelfDestruct, ChoiceSystem, CollisionHandler, CombativeCollisionHandler,
-    ExitSystem, SleepSystem,
+    Announcement, BossArenaSystem, BossDeathSystem, BuffExpirySystem, BulletSelfDestruct,
+    BurningTrailSystem, ChoiceSystem, CollisionHandler, CombativeCollisionHandler, ExitSystem,
+    FloorTransitionSystem, HealthConfig, LevelTeardownSystem, LifetimeSystem, PickupSystem,
+    RegenerationSystem, RetreatSystem, RewardSystem, SleepSystem,
 };
 
 mod render;
 use render::{
-    Render, RenderChoice, RenderCursor, RenderInventory, RenderUI, TileMapRender, WorldMapRender,
+    Render, RenderChoice, RenderCursor, RenderEncounter, RenderInventory, RenderShop, RenderUI,
+    TileMapRender, WorldMapRender,
 };
 
 mod prefabs;
-use prefabs::PrefabBuilder;
+use prefabs::PrefabRegistry;
+
+mod items;
+use items::ItemRegistry;
+
+mod active_items;
+use active_items::{ActiveItemSystem, TimeSlow, TurretSystem};
 
 mod level_generation;
-use level_generation::LevelStyle;
+use level_generation::{GenerationMetrics, LevelStyle};
+
+mod level_preview;
 
 mod world_generation;
 
+mod campaign;
+use campaign::Campaign;
+mod damage;
+mod encounters;
+use encounters::{CurrentEncounter, Encounter, EncounterSystem};
+mod shop;
+mod status_effects;
+use status_effects::StatusEffectSystem;
 mod world_map;
-use world_map::{CurrentDungeon, Dungeon, WorldMapScreen};
+use shop::{Shop, ShopSystem};
+use world_map::{Caravan, CurrentDungeon, Dungeon, WorldMapScreen};
 
 mod enemy_ai;
 use enemy_ai::{ChodeDeath, RunBossAI, RunChodeAI, RunShotgunnerAI, RunSpinnerAI};
@@ -55,20 +75,26 @@ mod sound;
 use sound::{SoundQueue, SoundSystem};
 
 mod all_components {
+    pub use crate::active_items::{ActiveItem, Turret};
+    pub use crate::damage::{Damage, Resistances};
     pub use crate::enemy_ai::{Boss, BossAttack, ChodeAI, ShotgunnerAI, SpinnerAI};
     pub use crate::gameplay::{
-        Asleep, Combative, Destructable, Exit, LevelObject, PenetratingBullet, Team, TeamWrap,
+        Asleep, BurningTrail, Combative, Destructable, Exit, LevelObject, Lifetime, Loot,
+        PenetratingBullet, Pickup, PickupKind, Team, TeamWrap,
+    };
+    pub use crate::physics::{
+        Airborne, Bullet, CollidingWithWall, HitBox, PhysicsComponent, Transform,
     };
-    pub use crate::physics::{Bullet, CollidingWithWall, HitBox, PhysicsComponent, Transform};
     pub use crate::player::PlayerControls;
     pub use crate::render::RenderComponent;
+    pub use crate::status_effects::{StatusEffects, StatusKind};
     pub use crate::Camera;
 }
 use all_components::*;
 
 mod prelude {
     pub use crate::physics::Transform;
-    pub use crate::prefabs::PrefabBuilder;
+    pub use crate::prefabs::PrefabRegistry;
     pub use crate::sound::{SoundQueue, SoundRequest};
     pub use crate::{SimTime, Timer};
     pub use quicksilver::geom::*;
@@ -91,7 +117,10 @@ pub struct Timer {
 
 #[derive(Default)]
 pub struct PlayerProgression {
-    pub range_extended: bool,
+    // Oases up to this tier are unlocked.
+    pub tier: i32,
+    pub currency: i32,
+    pub dungeons_completed: i32,
 }
 
 impl Timer {
@@ -123,6 +152,7 @@ pub struct Input {
     down: bool,
     fire: bool,
     dodge: bool,
+    use_active: bool,
     raw_mouse_pos: Vector,
     mouse_pos: Vector,
     clicked: bool,
@@ -132,6 +162,11 @@ pub struct Input {
 pub enum Event {
     Collision(Entity, Entity),
     EntityKilled(Entity),
+    // The player reached the stairs, either by walking there or by a debug warp.
+    ExitReached,
+    DungeonCompleted,
+    Retreat,
+    ActiveItemUsed(Entity, ActiveItem),
 }
 
 #[derive(Debug, Default, Clone)]
@@ -162,6 +197,8 @@ pub enum UIState {
     GameOver,
     Victory,
     Choice,
+    Shop,
+    Encounter,
     Help(Box<UIState>, String, String),
 }
 
@@ -229,8 +266,9 @@ impl<'a> System<'a> for CameraSystem {
     }
 }
 
-fn create_world() -> World {
-    let level = level_generation::generate_level(LevelStyle::Cyclic);
+fn create_world(campaign: Campaign) -> World {
+    let mut generation_metrics = GenerationMetrics::default();
+    let level = level_generation::generate_level(LevelStyle::Cyclic, &mut generation_metrics);
     let mut world = World::new();
 
     world.register::<Transform>();
@@ -241,6 +279,7 @@ fn create_world() -> World {
     world.register::<Bullet>();
     world.register::<CollidingWithWall>();
     world.register::<Dungeon>();
+    world.register::<Encounter>();
     world.register::<Exit>();
     world.register::<Destructable>();
     world.register::<LevelObject>();
@@ -253,25 +292,31 @@ fn create_world() -> World {
     world.register::<Camera>();
     world.register::<ShotgunnerAI>();
     world.register::<SpinnerAI>();
-
-    let player = world
-        .create_entity()
-        .with_player_prefab()
+    world.register::<Pickup>();
+    world.register::<Turret>();
+    world.register::<Lifetime>();
+    world.register::<BurningTrail>();
+    world.register::<Loot>();
+    world.register::<StatusEffects>();
+    world.register::<Resistances>();
+    world.register::<Airborne>();
+
+    let prefabs = PrefabRegistry::load();
+    let player = prefabs
+        .spawn("player", world.create_entity())
         .with(Transform {
             position: Vector::from(level.start_position) * TILE_SIZE
                 + Vector::new(TILE_SIZE / 2.0, TILE_SIZE / 2.0),
         })
         .build();
-    world
-        .create_entity()
-        .with_target_prefab()
+    prefabs
+        .spawn("target", world.create_entity())
         .with(Transform {
             position: Vector::new(SCREEN_WIDTH / 2.0, 100.0),
         })
         .build();
-    world
-        .create_entity()
-        .with_camera_prefab()
+    prefabs
+        .spawn("camera", world.create_entity())
         .with(Camera { follow: player })
         .build();
     world.add_resource::<Input>(Default::default());
@@ -283,8 +328,18 @@ fn create_world() -> World {
     world.add_resource::<PlayerProgression>(Default::default());
     world.add_resource::<CurrentDungeon>(Default::default());
     world.add_resource::<SoundQueue>(Default::default());
-
-    world_generation::generate_dungeons(&mut world);
+    world.add_resource(generation_metrics);
+    world.add_resource(ItemRegistry::load());
+    world.add_resource(prefabs);
+    world.add_resource::<TimeSlow>(Default::default());
+    world.add_resource::<Announcement>(Default::default());
+    world.add_resource::<Shop>(Default::default());
+    world.add_resource::<HealthConfig>(Default::default());
+    world.add_resource::<Caravan>(Default::default());
+    world.add_resource::<CurrentEncounter>(Default::default());
+    world.add_resource(campaign);
+
+    world_generation::generate_desert(&mut world);
     world
 }
 
@@ -300,7 +355,7 @@ impl State for GameState {
         let font =
             Font::from_slice(include_bytes!("fonts/fonts/OpenSans/OpenSans-Regular.ttf")).unwrap();
 
-        let world = create_world();
+        let world = create_world(Campaign::load(campaign::STANDARD));
 
         Ok(GameState {
             world,
@@ -320,6 +375,7 @@ impl State for GameState {
             fire: window.mouse()[MouseButton::Left].is_down(),
             dodge: window.mouse()[MouseButton::Right].is_down()
                 || window.keyboard()[Key::LShift].is_down(),
+            use_active: window.keyboard()[Key::Space] == ButtonState::Pressed,
             raw_mouse_pos: window.mouse().pos(),
             mouse_pos: Vector::new(-1.0, -1.0),
             clicked: window.mouse()[MouseButton::Left] == ButtonState::Pressed,
@@ -335,8 +391,13 @@ impl State for GameState {
                 if window.keyboard()[Key::Escape] == ButtonState::Pressed {
                     window.close();
                 }
-                if window.keyboard()[Key::Space] == ButtonState::Pressed {
-                    self.world.add_resource(UIState::Help(Box::new(UIState::WorldMap), "World Map".to_string(), "You are searching for the Ankh of Yendor.\nBut alas you have got lost in the desert,\n stranded at an oasis.\n\nThe orange square is a dungeon containing\n an item needed to progress in the game,\n but it is treacherous.\nConsider visiting other dungeons\n in range to stock up on magic items.".to_string()));
+                if window.keyboard()[Key::Q] == ButtonState::Pressed {
+                    self.world = create_world(Campaign::load(campaign::QUICK_RUN));
+                }
+                if window.keyboard()[Key::Space] == ButtonState::Pressed
+                    || window.keyboard()[Key::Q] == ButtonState::Pressed
+                {
+                    self.world.add_resource(UIState::Help(Box::new(UIState::WorldMap), "World Map".to_string(), "You are searching for the Ankh of Yendor.\nBut alas you have got lost in the desert,\n stranded at an oasis.\n\nThe orange square is a dungeon containing\n an item needed to progress in the game,\n but it is treacherous.\nConsider visiting other dungeons\n in range to stock up on magic items.\n\nTravel costs a day of water per step,\n and going thirsty hurts.\nClick the oasis to refill and spend your gold.".to_string()));
                 }
                 Ok(())
             }
@@ -357,7 +418,7 @@ impl State for GameState {
             UIState::Playing => {
                 if !self.shown_playing_help {
                     self.shown_playing_help = true;
-                    self.world.add_resource(UIState::Help(Box::new(UIState::Playing), "Exploration".to_string(), "Controls:\nWASD to move\nMouse to aim\nLeft click to fire\nRight click to dash\n\nLook for the stairs down(Green circle)".to_string()));
+                    self.world.add_resource(UIState::Help(Box::new(UIState::Playing), "Exploration".to_string(), "Controls:\nWASD to move\nMouse to aim\nLeft click to fire\nRight click to dash\nSpace to use your active item\n\nLook for the stairs down(Green circle)".to_string()));
                     return Ok(());
                 }
                 if window.keyboard()[Key::Escape] == ButtonState::Pressed {
@@ -366,13 +427,23 @@ impl State for GameState {
                         .write_resource::<SoundQueue>()
                         .enqueue(SoundRequest::Pause);
                 }
+                if cfg!(debug_assertions)
+                    && window.keyboard()[Key::F2] == ButtonState::Pressed
+                    && can_warp(&self.world)
+                {
+                    self.world
+                        .write_resource::<EventQueue>()
+                        .enqueue(Event::ExitReached);
+                }
                 let mut sim_time = *self.world.read_resource::<SimTime>();
                 sim_time.time += 1.0 / 60.0; // Quicksilver tries to call at 60fps
                 sim_time.dt = 1.0 / 60.0;
                 self.world.add_resource(sim_time);
-                self.world.write_resource::<EventQueue>().clear();
                 self.dispatcher.dispatch(&self.world.res);
                 self.world.maintain();
+                // Cleared after rather than before so events raised outside the dispatcher (menus,
+                // debug keys) get handled on the next tick.
+                self.world.write_resource::<EventQueue>().clear();
                 Ok(())
             }
             UIState::Pause => {
@@ -382,13 +453,19 @@ impl State for GameState {
                         .write_resource::<SoundQueue>()
                         .enqueue(SoundRequest::Pause);
                 }
+                if window.keyboard()[Key::R] == ButtonState::Pressed && can_retreat(&self.world) {
+                    self.world
+                        .write_resource::<EventQueue>()
+                        .enqueue(Event::Retreat);
+                    self.world.add_resource(UIState::Playing);
+                }
                 Ok(())
             }
             UIState::Victory | UIState::GameOver => {
                 if window.keyboard()[Key::Space] == ButtonState::Pressed
                     || window.keyboard()[Key::Escape] == ButtonState::Pressed
                 {
-                    self.world = create_world();
+                    self.world = create_world(Campaign::load(campaign::STANDARD));
                     self.world.add_resource(UIState::Title);
                 }
                 Ok(())
@@ -397,6 +474,18 @@ impl State for GameState {
                 ChoiceSystem.run_now(&self.world.res);
                 Ok(())
             }
+            UIState::Shop => {
+                if window.keyboard()[Key::Escape] == ButtonState::Pressed {
+                    self.world.add_resource(UIState::WorldMap);
+                    return Ok(());
+                }
+                ShopSystem.run_now(&self.world.res);
+                Ok(())
+            }
+            UIState::Encounter => {
+                EncounterSystem.run_now(&self.world.res);
+                Ok(())
+            }
         }
     }
 
@@ -415,7 +504,13 @@ impl State for GameState {
                     Ok(())
                 })?;
                 draw_text_centered("Space to Start", Vector::new(400, 350), &self.font, window);
-                draw_text_centered("Esc to Quit", Vector::new(400, 400), &self.font, window);
+                draw_text_centered(
+                    "Q for a Quick Run",
+                    Vector::new(400, 400),
+                    &self.font,
+                    window,
+                );
+                draw_text_centered("Esc to Quit", Vector::new(400, 450), &self.font, window);
                 Ok(())
             }
             UIState::Help(_, title, text) => {
@@ -435,7 +530,10 @@ impl State for GameState {
                 Ok(())
             }
             UIState::WorldMap => {
-                let mut world_map_render = WorldMapRender { window };
+                let mut world_map_render = WorldMapRender {
+                    window,
+                    font: &self.font,
+                };
                 world_map_render.run_now(&self.world.res);
                 Ok(())
             }
@@ -481,6 +579,14 @@ impl State for GameState {
                     &self.font,
                     window,
                 );
+                if can_retreat(&self.world) {
+                    draw_text_centered(
+                        "Press R to retreat to the oasis",
+                        Vector::new(400, 450),
+                        &self.font,
+                        window,
+                    );
+                }
                 let mut render_inventory = RenderInventory {
                     window,
                     font: &self.font,
@@ -498,13 +604,56 @@ impl State for GameState {
                 render_cursor.run_now(&self.world.res);
                 Ok(())
             }
+            UIState::Shop => {
+                let mut render_shop = RenderShop {
+                    window,
+                    font: &self.font,
+                };
+                render_shop.run_now(&self.world.res);
+                Ok(())
+            }
+            UIState::Encounter => {
+                let mut render_encounter = RenderEncounter {
+                    window,
+                    font: &self.font,
+                };
+                render_encounter.run_now(&self.world.res);
+                let mut render_cursor = RenderCursor { window };
+                render_cursor.run_now(&self.world.res);
+                Ok(())
+            }
         }
     }
 }
 
+// There's no running from the boss.
+fn can_retreat(world: &World) -> bool {
+    world.read_storage::<Boss>().join().next().is_none()
+}
+
+// The debug warp skips floors, so it does nothing in the boss arena or once the dungeon is done.
+fn can_warp(world: &World) -> bool {
+    let current_dungeon = world.read_resource::<CurrentDungeon>();
+    let completed = current_dungeon
+        .entity
+        .and_then(|d| world.read_storage::<Dungeon>().get(d).map(|d| d.completed))
+        .unwrap_or(true);
+    !completed && can_retreat(world)
+}
+
 fn draw_text_centered(text: &str, position: Vector, font: &Font, window: &mut Window) {
+    draw_text_centered_with_size(text, position, 32.0, font, window);
+}
+
+fn draw_text_centered_with_size(
+    text: &str,
+    position: Vector,
+    size: f32,
+    font: &Font,
+    window: &mut Window,
+) {
     let img = font
-        .render(text, &FontStyle::new(32.0, Color::WHITE))
+        .render(text, &FontStyle::new(size, Color::WHITE))
         .unwrap();
     let mut rect = img.area();
     rect.pos = position - rect.size / 2.0;
@@ -520,6 +669,8 @@ fn make_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
         .with(RunShotgunnerAI, "run_shotgunner_ai", &[])
         .with(RunSpinnerAI, "run_spinner_ai", &[])
         .with(RunBossAI, "run_boss_ai", &[])
+        .with(TurretSystem, "turret", &[])
+        .with(ActiveItemSystem, "active_items", &["player_control"])
         .with(
             PhysicsSystem,
             "physics",
@@ -542,19 +693,26 @@ fn make_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
             "combative_collision_handler",
             &["collision_detection"],
         )
-        .with(ChodeDeath, "chode_death", &["combative_collision_handler"])
         .with(
-            BossDeathSystem,
-            "boss_death",
-            &["combative_collision_handler"],
-        )
-        .with(
-            PlayerDeath,
-            "player_death",
+            StatusEffectSystem,
+            "status_effects",
             &["combative_collision_handler"],
         )
+        .with(ChodeDeath, "chode_death", &["status_effects"])
+        .with(BossDeathSystem, "boss_death", &["status_effects"])
+        .with(PlayerDeath, "player_death", &["status_effects"])
         .with(BulletSelfDestruct, "bullet_self_destruct", &["physics"])
         .with(ExitSystem, "exit", &["physics"])
+        .with(LevelTeardownSystem, "level_teardown", &["exit"])
+        .with(FloorTransitionSystem, "floor_transition", &["exit"])
+        .with(RewardSystem, "reward", &["floor_transition"])
+        .with(BossArenaSystem, "boss_arena", &["floor_transition"])
+        .with(RetreatSystem, "retreat", &[])
+        .with(PickupSystem, "pickup", &["physics"])
+        .with(BuffExpirySystem, "buff_expiry", &["pickup"])
+        .with(RegenerationSystem, "regeneration", &["status_effects"])
+        .with(LifetimeSystem, "lifetime", &[])
+        .with(BurningTrailSystem, "burning_trail", &["physics"])
         .with(
             SleepSystem,
             "sleep_system",
@@ -565,6 +723,12 @@ fn make_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    level_generation::load_data();
+    if args.get(1).map(String::as_str) == Some("levelgen") {
+        level_preview::run(&args[2..]);
+        return;
+    }
     run::<GameState>(
         "Specs Test",
         quicksilver::geom::Vector::new(SCREEN_WIDTH, SCREEN_HEIGHT),
@@ -1,5 +1,8 @@
+use crate::active_items::TimeSlow;
+use crate::damage::Damage;
 use crate::gameplay::TeamWrap;
 use crate::prelude::*;
+use crate::status_effects::{StatusEffects, StatusKind};
 use crate::{Event, EventQueue};
 use std::collections::HashMap;
 
@@ -38,8 +41,10 @@ impl Component for Transform {
 #[derive(Debug, Default)]
 pub struct Bullet {
     pub radius: f32,
-    pub damage: i32,
+    pub damage: Damage,
     pub penetrating: bool,
+    // Applied to whatever the bullet damages.
+    pub effects: Vec<StatusKind>,
 }
 
 impl Component for Bullet {
@@ -56,6 +61,14 @@ impl Component for HitBox {
     type Storage = VecStorage<Self>;
 }
 
+// Currently in the air (e.g. dashing), so pits don't block it.
+#[derive(Default)]
+pub struct Airborne;
+
+impl Component for Airborne {
+    type Storage = HashMapStorage<Self>;
+}
+
 pub struct CollidingWithWall;
 
 impl Component for CollidingWithWall {
@@ -73,6 +86,10 @@ impl<'a> System<'a> for PhysicsSystem {
         ReadStorage<'a, HitBox>,
         Read<'a, TileMap>,
         WriteStorage<'a, CollidingWithWall>,
+        ReadStorage<'a, TeamWrap>,
+        Read<'a, TimeSlow>,
+        ReadStorage<'a, StatusEffects>,
+        ReadStorage<'a, Airborne>,
     );
 
     fn run(
@@ -85,23 +102,43 @@ impl<'a> System<'a> for PhysicsSystem {
             hitboxes,
             tilemap,
             mut colliding_with_walls,
+            teams,
+            time_slow,
+            statuses,
+            airbornes,
         ): Self::SystemData,
     ) {
-        for physics in (&mut physics).join() {
-            physics.velocity += physics.acceleration * sim_time.dt;
-            if physics.velocity.len2() >= physics.max_speed * physics.max_speed {
-                physics.velocity = physics.velocity.with_len(physics.max_speed);
+        for (physics, team, status) in (&mut physics, teams.maybe(), statuses.maybe()).join() {
+            if status.map_or(false, |s| s.immobilised()) {
+                physics.velocity = Vector::new(0.0, 0.0);
+                continue;
+            }
+            physics.velocity += physics.acceleration * time_slow.dt(*sim_time, team);
+            let max_speed = physics.max_speed * status.map_or(1.0, |s| s.speed_multiplier());
+            if physics.velocity.len2() >= max_speed * max_speed {
+                physics.velocity = physics.velocity.with_len(max_speed);
             }
         }
 
-        for (transform, physics, hitbox) in (&mut transforms, &mut physics, &hitboxes).join() {
+        for (transform, physics, hitbox, team, airborne) in (
+            &mut transforms,
+            &mut physics,
+            &hitboxes,
+            teams.maybe(),
+            airbornes.maybe(),
+        )
+            .join()
+        {
+            let airborne = airborne.is_some();
             let round_position = (transform.position.x.floor(), transform.position.y.floor());
-            assert!(!check_collision(
+            assert!(!collides(
                 Vector::from(round_position),
                 hitbox,
-                &tilemap
+                &tilemap,
+                airborne
             ));
-            let new_position = transform.position + physics.velocity * sim_time.dt;
+            let new_position =
+                transform.position + physics.velocity * time_slow.dt(*sim_time, team);
             let old_x = transform.position.x.floor() as i32;
             let new_x = new_position.x.floor() as i32;
             let dx = sign(new_x - old_x);
@@ -109,8 +146,12 @@ impl<'a> System<'a> for PhysicsSystem {
             let mut hit = false;
             for ix in 0..steps {
                 let x = old_x + (1 + ix) * dx;
-                let colliding =
-                    check_collision(Vector::new(x as f32, round_position.1), hitbox, &tilemap);
+                let colliding = collides(
+                    Vector::new(x as f32, round_position.1),
+                    hitbox,
+                    &tilemap,
+                    airborne,
+                );
                 if !colliding {
                     transform.position.x = x as f32;
                 } else {
@@ -130,8 +171,12 @@ impl<'a> System<'a> for PhysicsSystem {
             let mut hit = false;
             for iy in 0..steps {
                 let y = old_y + (1 + iy) * dy;
-                let colliding =
-                    check_collision(Vector::new(round_position.0, y as f32), hitbox, &tilemap);
+                let colliding = collides(
+                    Vector::new(round_position.0, y as f32),
+                    hitbox,
+                    &tilemap,
+                    airborne,
+                );
                 if !colliding {
                     transform.position.y = y as f32;
                 } else {
@@ -143,17 +188,25 @@ impl<'a> System<'a> for PhysicsSystem {
             if !hit {
                 transform.position.y = new_position.y;
             }
-            assert!(!check_collision(
+            assert!(!collides(
                 Vector::new(transform.position.x.floor(), transform.position.y.floor()),
                 hitbox,
-                &tilemap
+                &tilemap,
+                airborne
             ));
         }
 
-        for (entity, transform, physics, _) in
-            (&entities, &mut transforms, &physics, !&hitboxes).join()
+        for (entity, transform, physics, team, _) in (
+            &entities,
+            &mut transforms,
+            &physics,
+            teams.maybe(),
+            !&hitboxes,
+        )
+            .join()
         {
-            let new_position = transform.position + physics.velocity * sim_time.dt;
+            let new_position =
+                transform.position + physics.velocity * time_slow.dt(*sim_time, team);
             transform.position = new_position;
 
             let colliding = check_point_collision(new_position, &tilemap);
@@ -171,15 +224,20 @@ impl<'a> System<'a> for PhysicsSystem {
 fn check_point_collision(position: Vector, tilemap: &TileMap) -> bool {
     let tile_x = (position.x / TILE_SIZE).floor() as i32;
     let tile_y = (position.y / TILE_SIZE).floor() as i32;
-    tilemap
+    let tile = tilemap
         .tiles
         .get(&(tile_x, tile_y))
         .cloned()
-        .unwrap_or_default()
-        .collision
+        .unwrap_or_default();
+    tile.collision && !tile.pit
 }
 
 pub fn check_collision(position: Vector, hitbox: &HitBox, tilemap: &TileMap) -> bool {
+    collides(position, hitbox, tilemap, false)
+}
+
+// Airborne things pass over pits like bullets do.
+pub fn collides(position: Vector, hitbox: &HitBox, tilemap: &TileMap, airborne: bool) -> bool {
     let min_x = position.x - hitbox.radius;
     let max_x = position.x + hitbox.radius;
     let min_y = position.y - hitbox.radius;
@@ -192,12 +250,13 @@ pub fn check_collision(position: Vector, hitbox: &HitBox, tilemap: &TileMap) ->
 
     for tile_x in min_tile_x..=max_tile_x {
         for tile_y in min_tile_y..=max_tile_y {
-            if tilemap
+            let tile = tilemap
                 .tiles
                 .get(&(tile_x, tile_y))
                 .cloned()
-                .unwrap_or_default()
-                .collision
+                .unwrap_or_default();
+            if tile.collision
+                && !(airborne && tile.pit)
                 && hitcircle.overlaps(&Rectangle::new(
                     (tile_x as f32 * TILE_SIZE, (tile_y) as f32 * TILE_SIZE),
                     (TILE_SIZE, TILE_SIZE),
@@ -249,6 +308,8 @@ pub struct TileMap {
 #[derive(Copy, Clone)]
 pub struct Tile {
     pub collision: bool,
+    // Pits stop anything walking over them but bullets fly straight across.
+    pub pit: bool,
     pub colour: Color,
 }
 
@@ -256,6 +317,7 @@ impl Default for Tile {
     fn default() -> Self {
         Tile {
             collision: false,
+            pit: false,
             colour: Color::MAGENTA,
         }
     }
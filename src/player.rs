@@ -1,25 +1,81 @@
-use crate::gameplay::{Team, TeamWrap};
-use crate::physics::{check_collision, Bullet, HitBox, PhysicsComponent, TileMap};
+use crate::active_items::ActiveItem;
+use crate::gameplay::{BurningTrail, Team, TeamWrap};
+use crate::gameplay::{Combative, Lifetime};
+use crate::items::{Buff, Item, PlayerStats};
+use crate::physics::{
+    check_collision, collides, Airborne, Bullet, HitBox, PhysicsComponent, TileMap,
+};
 use crate::prelude::*;
 use crate::sound::{SoundQueue, SoundRequest};
-use crate::world_map::Item;
+use crate::status_effects::{StatusEffects, StatusKind};
 use crate::{Event, EventQueue, UIState};
 use crate::{Input, SimTime, Timer};
 
 const PLAYER_ACCELERATION: f32 = 1000.0;
-const DODGE_DISTANCE: i32 = 45;
+// The dash is spread over this many ticks rather than being a teleport.
+const DASH_TICKS: i32 = 5;
+const AFTERIMAGE_LIFETIME: f32 = 0.2;
+const RING_SHOT_BULLETS: i32 = 12;
 
 #[derive(Default)]
 pub struct PlayerControls {
-    pub fire_rate: f32,
     pub fire_cooldown: Timer,
     pub dodge_cooldown: Timer,
-    pub triple_shot: bool,
-    pub bullet_damage: i32,
-    pub penetrating: bool,
-    pub dodge_cooldown_time: f32,
-    pub backfire: bool,
+    pub base_stats: PlayerStats,
+    // Base stats with all item modifiers applied.
+    pub stats: PlayerStats,
     pub items_acquired: Vec<Item>,
+    pub active_item: Option<ActiveItem>,
+    pub active_cooldown: Timer,
+    pub buffs: Vec<(Buff, Timer)>,
+    pub dash: Option<Dash>,
+    // Progress towards the next hit point from regeneration.
+    pub regen_progress: f32,
+}
+
+#[derive(Copy, Clone)]
+pub struct Dash {
+    direction: (i32, i32),
+    ticks_left: i32,
+    pixels_per_tick: i32,
+    airborne: bool,
+    // Last position that wasn't over a pit, so a dash never ends in one.
+    last_safe: Vector,
+}
+
+// Moves the player along the dash one tick's worth. Returns true when the dash is over.
+fn continue_dash(
+    dash: &mut Dash,
+    transform: &mut Transform,
+    hitbox: &HitBox,
+    tile_map: &TileMap,
+) -> bool {
+    let mut position = (
+        transform.position.x.floor() as i32,
+        transform.position.y.floor() as i32,
+    );
+    let mut blocked = false;
+    for _ in 0..dash.pixels_per_tick {
+        let new_position = (position.0 + dash.direction.0, position.1 + dash.direction.1);
+        let new_vector = Vector::new(new_position.0 as f32, new_position.1 as f32);
+        if collides(new_vector, hitbox, tile_map, dash.airborne) {
+            blocked = true;
+            break;
+        }
+        position = new_position;
+        if !check_collision(new_vector, hitbox, tile_map) {
+            dash.last_safe = new_vector;
+        }
+    }
+    transform.position = Vector::new(position.0 as f32, position.1 as f32);
+    dash.ticks_left -= 1;
+    if blocked || dash.ticks_left <= 0 {
+        if check_collision(transform.position, hitbox, tile_map) {
+            transform.position = dash.last_safe;
+        }
+        return true;
+    }
+    false
 }
 
 impl Component for PlayerControls {
@@ -38,9 +94,14 @@ impl<'a> System<'a> for PlayerControlSystem {
         Read<'a, Input>,
         Read<'a, SimTime>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Read<'a, TileMap>,
         Entities<'a>,
         Write<'a, SoundQueue>,
+        Write<'a, EventQueue>,
+        ReadStorage<'a, StatusEffects>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, Airborne>,
     );
 
     fn run(
@@ -53,9 +114,14 @@ impl<'a> System<'a> for PlayerControlSystem {
             input,
             sim_time,
             lazy_update,
+            prefabs,
             tile_map,
             entities,
             mut sound_queue,
+            mut event_queue,
+            statuses,
+            mut combatives,
+            mut airbornes,
         ): Self::SystemData,
     ) {
         for (player_controls, transform, physics, player_ent) in (
@@ -67,6 +133,32 @@ impl<'a> System<'a> for PlayerControlSystem {
             .join()
         {
             physics.acceleration = Vector::new(0.0, 0.0);
+            // No steering, shooting or anything else until the dash is finished.
+            if let Some(mut dash) = player_controls.dash {
+                physics.velocity = Vector::new(0.0, 0.0);
+                prefabs
+                    .spawn("afterimage", lazy_update.create_entity(&entities))
+                    .with(Transform {
+                        position: transform.position,
+                    })
+                    .with(Lifetime {
+                        expires: Timer::new_set(*sim_time, AFTERIMAGE_LIFETIME),
+                    })
+                    .build();
+                let hitbox = hitboxes
+                    .get(player_ent)
+                    .expect("Only players with hitboxes can dash.");
+                if continue_dash(&mut dash, transform, hitbox, &tile_map) {
+                    player_controls.dash = None;
+                    airbornes.remove(player_ent);
+                } else {
+                    player_controls.dash = Some(dash);
+                }
+                continue;
+            }
+            if statuses.get(player_ent).map_or(false, |s| s.immobilised()) {
+                continue;
+            }
             let mut dx = 0;
             let mut dy = 0;
             if input.left {
@@ -94,77 +186,40 @@ impl<'a> System<'a> for PlayerControlSystem {
             }
             if input.fire && player_controls.fire_cooldown.expired(*sim_time) {
                 let bullet_speed = 400.0;
-                let velocity = (input.mouse_pos - transform.position).with_len(bullet_speed);
-                let position = transform.position + velocity.with_len(30.0);
-                lazy_update
-                    .create_entity(&entities)
-                    .with_bullet_prefab()
-                    .with(Bullet {
-                        radius: 5.0,
-                        damage: player_controls.bullet_damage,
-                        penetrating: player_controls.penetrating,
-                    })
-                    .with(Transform { position })
-                    .with(PhysicsComponent {
-                        velocity,
-                        max_speed: bullet_speed,
-                        ..Default::default()
-                    })
-                    .with(TeamWrap { team: Team::Player })
-                    .build();
-                if player_controls.triple_shot {
-                    let velocity =
-                        Vector::from_angle((input.mouse_pos - transform.position).angle() + 20.0)
-                            .with_len(bullet_speed);
-                    let position = transform.position + velocity.with_len(30.0);
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_bullet_prefab()
-                        .with(Bullet {
-                            radius: 5.0,
-                            damage: player_controls.bullet_damage,
-                            penetrating: player_controls.penetrating,
-                        })
-                        .with(Transform { position })
-                        .with(PhysicsComponent {
-                            velocity,
-                            max_speed: bullet_speed,
-                            ..Default::default()
-                        })
-                        .with(TeamWrap { team: Team::Player })
-                        .build();
-
-                    let velocity =
-                        Vector::from_angle((input.mouse_pos - transform.position).angle() - 20.0)
-                            .with_len(bullet_speed);
-                    let position = transform.position + velocity.with_len(30.0);
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_bullet_prefab()
-                        .with(Bullet {
-                            radius: 5.0,
-                            damage: player_controls.bullet_damage,
-                            penetrating: player_controls.penetrating,
-                        })
-                        .with(Transform { position })
-                        .with(PhysicsComponent {
-                            velocity,
-                            max_speed: bullet_speed,
-                            ..Default::default()
-                        })
-                        .with(TeamWrap { team: Team::Player })
-                        .build();
+                let stats = &player_controls.stats;
+                // Angles relative to the mouse direction.
+                let angles: Vec<f32> = if stats.ring_shot {
+                    (0..RING_SHOT_BULLETS)
+                        .map(|i| i as f32 * 360.0 / RING_SHOT_BULLETS as f32)
+                        .collect()
+                } else {
+                    let mut angles = vec![0.0];
+                    if stats.triple_shot {
+                        angles.extend_from_slice(&[20.0, -20.0]);
+                    }
+                    if stats.backfire {
+                        angles.push(180.0);
+                    }
+                    angles
+                };
+                let mut effects = Vec::new();
+                if stats.venomous {
+                    effects.push(StatusKind::Poison);
+                }
+                if stats.freezing {
+                    effects.push(StatusKind::Freeze);
                 }
-                if player_controls.backfire {
-                    let velocity = (transform.position - input.mouse_pos).with_len(bullet_speed);
+                let aim = (input.mouse_pos - transform.position).angle();
+                for angle in angles {
+                    let velocity = Vector::from_angle(aim + angle).with_len(bullet_speed);
                     let position = transform.position + velocity.with_len(30.0);
-                    lazy_update
-                        .create_entity(&entities)
-                        .with_bullet_prefab()
+                    let mut builder = prefabs
+                        .spawn("bullet", lazy_update.create_entity(&entities))
                         .with(Bullet {
                             radius: 5.0,
-                            damage: player_controls.bullet_damage,
-                            penetrating: player_controls.penetrating,
+                            damage: stats.damage(),
+                            penetrating: stats.penetrating,
+                            effects: effects.clone(),
                         })
                         .with(Transform { position })
                         .with(PhysicsComponent {
@@ -172,13 +227,24 @@ impl<'a> System<'a> for PlayerControlSystem {
                             max_speed: bullet_speed,
                             ..Default::default()
                         })
-                        .with(TeamWrap { team: Team::Player })
-                        .build();
+                        .with(TeamWrap { team: Team::Player });
+                    if stats.burning_trail {
+                        builder = builder.with(BurningTrail::default());
+                    }
+                    builder.build();
                 }
                 sound_queue.enqueue(SoundRequest::PlayerShot);
                 player_controls
                     .fire_cooldown
-                    .set(*sim_time, player_controls.fire_rate);
+                    .set(*sim_time, player_controls.stats.fire_rate);
+            }
+            if let Some(item) = player_controls.active_item {
+                if input.use_active && player_controls.active_cooldown.expired(*sim_time) {
+                    player_controls
+                        .active_cooldown
+                        .set(*sim_time, item.cooldown());
+                    event_queue.enqueue(Event::ActiveItemUsed(player_ent, item));
+                }
             }
             if input.dodge
                 && player_controls.dodge_cooldown.expired(*sim_time)
@@ -187,27 +253,31 @@ impl<'a> System<'a> for PlayerControlSystem {
             {
                 player_controls
                     .dodge_cooldown
-                    .set(*sim_time, player_controls.dodge_cooldown_time);
-                let hitbox = hitboxes.get(player_ent).unwrap();
-                let mut the_position = (
-                    transform.position.x.floor() as i32,
-                    transform.position.y.floor() as i32,
-                );
+                    .set(*sim_time, player_controls.stats.dodge_cooldown_time);
+                sound_queue.enqueue(SoundRequest::Dodge);
                 physics.velocity = Vector::new(0.0, 0.0);
-                for _i in 0..DODGE_DISTANCE {
-                    let new_position = (the_position.0 + dx, the_position.1 + dy);
-                    let colliding = check_collision(
-                        Vector::new(new_position.0 as f32, new_position.1 as f32),
-                        hitbox,
-                        &tile_map,
-                    );
-                    if !colliding {
-                        the_position = new_position
-                    } else {
-                        break;
+                let stats = &player_controls.stats;
+                if let Some(c) = combatives.get_mut(player_ent) {
+                    // Don't cut short a longer invincibility, like the shield's.
+                    if c.invincibility_cooldown.time_remaining(*sim_time) < stats.dash_iframes {
+                        c.invincibility_cooldown.set(*sim_time, stats.dash_iframes);
                     }
                 }
-                transform.position = Vector::new(the_position.0 as f32, the_position.1 as f32);
+                if stats.dash_over_pits {
+                    airbornes
+                        .insert(player_ent, Airborne)
+                        .expect("The player should be alive if they are dashing.");
+                }
+                player_controls.dash = Some(Dash {
+                    direction: (dx, dy),
+                    ticks_left: DASH_TICKS,
+                    pixels_per_tick: (stats.dash_distance / DASH_TICKS as f32).ceil() as i32,
+                    airborne: stats.dash_over_pits,
+                    last_safe: Vector::new(
+                        transform.position.x.floor(),
+                        transform.position.y.floor(),
+                    ),
+                });
             }
         }
     }
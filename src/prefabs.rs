@@ -1,3 +1,279 @@
-#![allow(clippy::needless_update)]
+use crate::all_components::*;
+use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::ffi::OsStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
 
-include!(concat!(env!("OUT_DIR"), "/prefabs.rs"));
+// Prefabs are read from prefabs/*.ron when the game starts, so they can be tweaked without rebuilding.
+const PREFAB_DIR: &str = "prefabs";
+
+// The web build has no filesystem, so it gets the prefabs baked in instead.
+#[cfg(target_arch = "wasm32")]
+const PREFAB_SOURCES: &[(&str, &str)] = &[
+    (
+        "prefabs/afterimage.ron",
+        include_str!("../prefabs/afterimage.ron"),
+    ),
+    ("prefabs/boss.ron", include_str!("../prefabs/boss.ron")),
+    ("prefabs/buff.ron", include_str!("../prefabs/buff.ron")),
+    ("prefabs/bullet.ron", include_str!("../prefabs/bullet.ron")),
+    ("prefabs/camera.ron", include_str!("../prefabs/camera.ron")),
+    ("prefabs/chode.ron", include_str!("../prefabs/chode.ron")),
+    ("prefabs/dummy.ron", include_str!("../prefabs/dummy.ron")),
+    ("prefabs/exit.ron", include_str!("../prefabs/exit.ron")),
+    ("prefabs/fire.ron", include_str!("../prefabs/fire.ron")),
+    ("prefabs/gold.ron", include_str!("../prefabs/gold.ron")),
+    (
+        "prefabs/hard_chode.ron",
+        include_str!("../prefabs/hard_chode.ron"),
+    ),
+    (
+        "prefabs/hard_shotgunner.ron",
+        include_str!("../prefabs/hard_shotgunner.ron"),
+    ),
+    (
+        "prefabs/hard_spinner.ron",
+        include_str!("../prefabs/hard_spinner.ron"),
+    ),
+    ("prefabs/heart.ron", include_str!("../prefabs/heart.ron")),
+    ("prefabs/player.ron", include_str!("../prefabs/player.ron")),
+    ("prefabs/shield.ron", include_str!("../prefabs/shield.ron")),
+    (
+        "prefabs/shotgunner.ron",
+        include_str!("../prefabs/shotgunner.ron"),
+    ),
+    (
+        "prefabs/spinner.ron",
+        include_str!("../prefabs/spinner.ron"),
+    ),
+    ("prefabs/target.ron", include_str!("../prefabs/target.ron")),
+    ("prefabs/turret.ron", include_str!("../prefabs/turret.ron")),
+];
+
+// Looks next to the executable first so a packaged build can ship its own prefabs,
+// then falls back to the source tree so `cargo run` works from any directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn prefab_dir() -> PathBuf {
+    let beside_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(PREFAB_DIR)));
+    match beside_exe {
+        Some(dir) if dir.is_dir() => dir,
+        _ => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(PREFAB_DIR),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_sources() -> Vec<(String, String)> {
+    let dir = prefab_dir();
+    let entries = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Couldn't read prefab directory {}: {}", dir.display(), e));
+    let mut sources = Vec::new();
+    for entry in entries {
+        let path = entry
+            .unwrap_or_else(|e| panic!("Couldn't read prefab directory {}: {}", dir.display(), e))
+            .path();
+        if !path.is_file() || path.extension() != Some(OsStr::new("ron")) {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Couldn't read prefab {}: {}", path.display(), e));
+        sources.push((path.display().to_string(), source));
+    }
+    sources
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_sources() -> Vec<(String, String)> {
+    PREFAB_SOURCES
+        .iter()
+        .map(|(file, source)| (file.to_string(), source.to_string()))
+        .collect()
+}
+
+// Everything a prefab can be made of. Properties that are left out take the component's default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum ComponentData {
+    RenderComponent {
+        radius: f32,
+        // Red, green and blue from 0 to 255, then alpha from 0 to 1.
+        colour: (u8, u8, u8, f32),
+    },
+    HitBox {
+        radius: f32,
+    },
+    PhysicsComponent {
+        #[serde(default)]
+        max_speed: f32,
+    },
+    TeamWrap {
+        team: Team,
+    },
+    Combative {
+        max_hp: i32,
+    },
+    Resistances {
+        #[serde(default)]
+        kinetic: f32,
+        #[serde(default)]
+        fire: f32,
+        #[serde(default)]
+        arcane: f32,
+    },
+    Loot {
+        #[serde(default)]
+        heart_chance: f32,
+        #[serde(default)]
+        shield_chance: f32,
+        #[serde(default)]
+        gold_chance: f32,
+        #[serde(default)]
+        gold: i32,
+        #[serde(default)]
+        buff_chance: f32,
+    },
+    Bullet {
+        radius: f32,
+        damage: Damage,
+        #[serde(default)]
+        penetrating: bool,
+        #[serde(default)]
+        effects: Vec<StatusKind>,
+    },
+    Pickup {
+        #[serde(default)]
+        kind: PickupKind,
+    },
+    // Starts with the base stats.
+    PlayerControls {
+        #[serde(default)]
+        active_item: Option<ActiveItem>,
+    },
+    Transform,
+    Lifetime,
+    LevelObject,
+    ChodeAI,
+    ShotgunnerAI,
+    SpinnerAI,
+    Asleep,
+    Exit,
+    Destructable,
+    Turret,
+}
+
+impl ComponentData {
+    fn add_to<B: Builder>(&self, builder: B) -> B {
+        match self.clone() {
+            ComponentData::RenderComponent {
+                radius,
+                colour: (r, g, b, a),
+            } => builder.with(RenderComponent {
+                radius,
+                colour: rgba!(r, g, b, a),
+            }),
+            ComponentData::HitBox { radius } => builder.with(HitBox { radius }),
+            ComponentData::PhysicsComponent { max_speed } => builder.with(PhysicsComponent {
+                max_speed,
+                ..Default::default()
+            }),
+            ComponentData::TeamWrap { team } => builder.with(TeamWrap { team }),
+            ComponentData::Combative { max_hp } => builder.with(Combative {
+                max_hp,
+                ..Default::default()
+            }),
+            ComponentData::Resistances {
+                kinetic,
+                fire,
+                arcane,
+            } => builder.with(Resistances {
+                kinetic,
+                fire,
+                arcane,
+            }),
+            ComponentData::Loot {
+                heart_chance,
+                shield_chance,
+                gold_chance,
+                gold,
+                buff_chance,
+            } => builder.with(Loot {
+                heart_chance,
+                shield_chance,
+                gold_chance,
+                gold,
+                buff_chance,
+            }),
+            ComponentData::Bullet {
+                radius,
+                damage,
+                penetrating,
+                effects,
+            } => builder.with(Bullet {
+                radius,
+                damage,
+                penetrating,
+                effects,
+            }),
+            ComponentData::Pickup { kind } => builder.with(Pickup { kind }),
+            ComponentData::PlayerControls { active_item } => builder.with(PlayerControls {
+                active_item,
+                ..Default::default()
+            }),
+            ComponentData::Transform => builder.with(Transform::default()),
+            ComponentData::Lifetime => builder.with(Lifetime::default()),
+            ComponentData::LevelObject => builder.with(LevelObject),
+            ComponentData::ChodeAI => builder.with(ChodeAI::default()),
+            ComponentData::ShotgunnerAI => builder.with(ShotgunnerAI::default()),
+            ComponentData::SpinnerAI => builder.with(SpinnerAI::default()),
+            ComponentData::Asleep => builder.with(Asleep),
+            ComponentData::Exit => builder.with(Exit),
+            ComponentData::Destructable => builder.with(Destructable),
+            ComponentData::Turret => builder.with(Turret::default()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Prefab {
+    name: String,
+    components: Vec<ComponentData>,
+}
+
+#[derive(Default)]
+pub struct PrefabRegistry {
+    prefabs: HashMap<String, Vec<ComponentData>>,
+}
+
+impl PrefabRegistry {
+    pub fn load() -> PrefabRegistry {
+        let mut prefabs = HashMap::new();
+        for (file, source) in read_sources() {
+            let prefab: Prefab = ron::de::from_str(&source)
+                .unwrap_or_else(|e| panic!("Bad prefab definitions {}:{}", file, e));
+            if prefabs.contains_key(&prefab.name) {
+                panic!(
+                    "Bad prefab definitions {}: another prefab is already called '{}'",
+                    file, prefab.name
+                );
+            }
+            prefabs.insert(prefab.name, prefab.components);
+        }
+        PrefabRegistry { prefabs }
+    }
+
+    // Adds the components of the named prefab to `builder`, which can then be given more before building.
+    pub fn spawn<B: Builder>(&self, name: &str, builder: B) -> B {
+        let components = self
+            .prefabs
+            .get(name)
+            .unwrap_or_else(|| panic!("No prefab called '{}' in {}", name, PREFAB_DIR));
+        components
+            .iter()
+            .fold(builder, |builder, component| component.add_to(builder))
+    }
+}
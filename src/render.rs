@@ -1,10 +1,20 @@
+use crate::active_items::ActiveItem;
+use crate::encounters::{CurrentEncounter, Encounter};
 use crate::enemy_ai::Boss;
-use crate::gameplay::Combative;
+use crate::gameplay::{Announcement, Combative, HealthConfig};
+use crate::items::ItemRegistry;
 use crate::physics::{TileMap, TILE_SIZE};
 use crate::player::PlayerControls;
 use crate::prelude::*;
-use crate::world_map::{Dungeon, Reward, RANGE1, RANGE2};
-use crate::{draw_text_centered, Camera, CurrentDungeon, Input, PlayerProgression};
+use crate::shop::{Shop, ShopOffer};
+use crate::status_effects::StatusEffects;
+use crate::world_map::{
+    in_range, Caravan, ChoiceOption, DesertMap, Dungeon, Reward, Terrain, MAP_CELL,
+};
+use crate::{
+    draw_text_centered, draw_text_centered_with_size, Camera, CurrentDungeon, Input,
+    PlayerProgression,
+};
 use quicksilver::graphics::Font;
 use quicksilver::lifecycle::Window;
 
@@ -73,40 +83,51 @@ impl<'a: 'b, 'b> System<'b> for Render<'a> {
         ReadStorage<'b, Combative>,
         Entities<'b>,
         Read<'b, SimTime>,
+        ReadStorage<'b, StatusEffects>,
     );
 
     fn run(
         &mut self,
-        (camera, transforms, render, combatives, entities, sim_time): Self::SystemData,
+        (camera, transforms, render, combatives, entities, sim_time, statuses): Self::SystemData,
     ) {
         let mut camera_pos = Vector::new(-1.0, -1.0);
         for (_, camera_transform) in (&camera, &transforms).join() {
             camera_pos = camera_transform.position;
         }
         for (movement, render, ent) in (&transforms, &render, &entities).join() {
+            let colour = match statuses.get(ent).and_then(|s| s.tint()) {
+                Some(tint) => blend(render.colour, tint),
+                None => render.colour,
+            };
             if let Some(c) = combatives.get(ent) {
                 let time = c.invincibility_cooldown.time_remaining(*sim_time);
                 let time_int = (time / 0.05).floor() as i32;
                 if c.invincibility_cooldown.expired(*sim_time) || time_int % 2 == 0 {
                     let circle = Circle::new(movement.position - camera_pos, render.radius);
-                    self.window.draw(
-                        &circle,
-                        quicksilver::graphics::Background::Col(render.colour),
-                    );
+                    self.window
+                        .draw(&circle, quicksilver::graphics::Background::Col(colour));
                 } else {
                     // Don't render
                 }
             } else {
                 let circle = Circle::new(movement.position - camera_pos, render.radius);
-                self.window.draw(
-                    &circle,
-                    quicksilver::graphics::Background::Col(render.colour),
-                );
+                self.window
+                    .draw(&circle, quicksilver::graphics::Background::Col(colour));
             }
         }
     }
 }
 
+// Halfway between the two, keeping the base colour's alpha.
+fn blend(base: Color, tint: Color) -> Color {
+    Color {
+        r: (base.r + tint.r) / 2.0,
+        g: (base.g + tint.g) / 2.0,
+        b: (base.b + tint.b) / 2.0,
+        a: base.a,
+    }
+}
+
 pub struct RenderUI<'a> {
     pub window: &'a mut Window,
     pub font: &'a Font,
@@ -117,28 +138,123 @@ impl<'a: 'b, 'b> System<'b> for RenderUI<'a> {
         ReadStorage<'b, PlayerControls>,
         ReadStorage<'b, Combative>,
         ReadStorage<'b, Boss>,
+        Read<'b, CurrentDungeon>,
+        ReadStorage<'b, Dungeon>,
+        Read<'b, SimTime>,
+        ReadStorage<'b, StatusEffects>,
     );
 
-    fn run(&mut self, (players, combative, bosses): Self::SystemData) {
-        for (_, c) in (&players, &combative).join() {
+    fn run(
+        &mut self,
+        (players, combative, bosses, current_dungeon, dungeons, sim_time, statuses): Self::SystemData,
+    ) {
+        for (p, c) in (&players, &combative).join() {
+            draw_hearts(c, p.regen_progress, self.window);
+        }
+        for (_, c) in (&bosses, &combative).join() {
             draw_text_centered(
-                &format!("Health: {} / {}", c.max_hp - c.damage, c.max_hp),
-                Vector::new(100, 25),
+                &format!("Boss Health: {} / {}", c.max_hp - c.damage, c.max_hp),
+                Vector::new(100, 50),
                 &self.font,
                 self.window,
             );
         }
-        for (_, c) in (&bosses, &combative).join() {
+        if let Some(d) = current_dungeon.entity.and_then(|e| dungeons.get(e)) {
             draw_text_centered(
-                &format!("Boss Health: {} / {}", c.max_hp - c.damage, c.max_hp),
-                Vector::new(100, 50),
+                &format!("Floor {}/{}", d.current_floor, d.floors),
+                Vector::new(700, 25),
                 &self.font,
                 self.window,
             );
         }
+        for (p, status) in (&players, statuses.maybe()).join() {
+            if let Some(item) = p.active_item {
+                draw_active_item_hud(item, p.active_cooldown, *sim_time, self.font, self.window);
+            }
+            if let Some(status) = status {
+                for (i, effect) in status.effects.iter().enumerate() {
+                    let label = if effect.stacks > 1 {
+                        format!("{} x{}", effect.kind.name(), effect.stacks)
+                    } else {
+                        effect.kind.name().to_string()
+                    };
+                    draw_text_centered_with_size(
+                        &format!("{} {:.1}s", label, effect.expires.time_remaining(*sim_time)),
+                        Vector::new(100.0, 80.0 + 25.0 * i as f32),
+                        24.0,
+                        self.font,
+                        self.window,
+                    );
+                }
+            }
+            for (i, (buff, expires)) in p.buffs.iter().enumerate() {
+                draw_text_centered_with_size(
+                    &format!("{} {:.0}s", buff.name(), expires.time_remaining(*sim_time)),
+                    Vector::new(300 + 110 * i as i32, 570),
+                    24.0,
+                    self.font,
+                    self.window,
+                );
+            }
+        }
+    }
+}
+
+const HEART_RADIUS: f32 = 8.0;
+
+// Top left: a heart per hit point, then any shield on top. The first empty heart fills up with
+// regeneration.
+fn draw_hearts(c: &Combative, regen_progress: f32, window: &mut Window) {
+    let hp = c.hp().max(0);
+    for i in 0..(c.max_hp + c.shield) {
+        let centre = Vector::new(25.0 + 22.0 * i as f32, 25.0);
+        let colour = if i >= c.max_hp {
+            rgba!(140, 200, 255, 1.0)
+        } else if i < hp {
+            rgba!(220, 30, 50, 1.0)
+        } else {
+            rgba!(60, 60, 60, 1.0)
+        };
+        window.draw(
+            &Circle::new(centre, HEART_RADIUS),
+            quicksilver::graphics::Background::Col(colour),
+        );
+        if i == hp && i < c.max_hp && regen_progress > 0.0 {
+            window.draw(
+                &Circle::new(centre, HEART_RADIUS * regen_progress),
+                quicksilver::graphics::Background::Col(rgba!(220, 30, 50, 1.0)),
+            );
+        }
     }
 }
 
+// Bottom left: the active item and a bar that fills up as it recharges.
+fn draw_active_item_hud(
+    item: ActiveItem,
+    cooldown: Timer,
+    sim_time: SimTime,
+    font: &Font,
+    window: &mut Window,
+) {
+    let remaining = cooldown.time_remaining(sim_time).max(0.0);
+    let label = if remaining > 0.0 {
+        format!("{} {:.1}s", item.name(), remaining)
+    } else {
+        format!("{} (Space)", item.name())
+    };
+    draw_text_centered_with_size(&label, Vector::new(100, 550), 24.0, font, window);
+    let bar = Rectangle::new((25, 570), (150, 8));
+    window.draw(
+        &bar,
+        quicksilver::graphics::Background::Col(rgba!(60, 60, 60, 1.0)),
+    );
+    let charged = 1.0 - remaining / item.cooldown();
+    window.draw(
+        &Rectangle::new(bar.pos, (bar.size.x * charged, bar.size.y)),
+        quicksilver::graphics::Background::Col(item.colour()),
+    );
+}
+
 pub struct RenderCursor<'a> {
     pub window: &'a mut Window,
 }
@@ -190,6 +306,7 @@ impl<'a: 'b, 'b> System<'b> for RenderCursor<'a> {
 
 pub struct WorldMapRender<'a> {
     pub window: &'a mut Window,
+    pub font: &'a Font,
 }
 
 impl<'a: 'b, 'b> System<'b> for WorldMapRender<'a> {
@@ -197,97 +314,541 @@ impl<'a: 'b, 'b> System<'b> for WorldMapRender<'a> {
         Read<'b, Input>,
         ReadStorage<'b, Dungeon>,
         Read<'b, PlayerProgression>,
+        Read<'b, Announcement>,
+        Read<'b, Caravan>,
+        Read<'b, DesertMap>,
+        ReadStorage<'b, Encounter>,
+        Read<'b, ItemRegistry>,
     );
 
-    fn run(&mut self, (input, dungeons, progress): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            input,
+            dungeons,
+            progress,
+            announcement,
+            caravan,
+            desert_map,
+            encounters,
+            item_registry,
+        ): Self::SystemData,
+    ) {
         let screen_size = self.window.screen_size();
         let offset = screen_size / 2.0;
 
-        // Draw oasis
-        let circle = Circle::new(offset, 10.0);
-        self.window
-            .draw(&circle, quicksilver::graphics::Background::Col(Color::BLUE));
+        for x in 0..desert_map.width {
+            for y in 0..desert_map.height {
+                let colour = match desert_map.get(x, y) {
+                    Terrain::Sand => continue,
+                    Terrain::Dunes => rgba!(150, 120, 60, 1.0),
+                    Terrain::Rock => rgba!(80, 70, 65, 1.0),
+                };
+                let rect = Rectangle::new(
+                    desert_map.cell_position(x, y) + offset,
+                    Vector::new(MAP_CELL, MAP_CELL),
+                );
+                self.window
+                    .draw(&rect, quicksilver::graphics::Background::Col(colour));
+            }
+        }
 
-        for d in dungeons.join() {
-            let rect = Rectangle::new(d.position + offset, Vector::new(10.0, 10.0));
+        for oasis in desert_map.oases.iter() {
+            let unlocked = oasis.unlocked(&progress);
+            if unlocked {
+                let circle = Circle::new(oasis.position + offset, oasis.range);
+                self.window.draw(
+                    &circle,
+                    quicksilver::graphics::Background::Col(rgba!(0.0, 0.0, 250.0, 0.25)),
+                );
+            }
+            let circle = Circle::new(oasis.position + offset, 10.0);
             self.window.draw(
-                &rect,
-                quicksilver::graphics::Background::Col(if d.completed {
-                    Color::GREEN
-                } else if let Reward::Progress = d.reward {
-                    Color::ORANGE
+                &circle,
+                quicksilver::graphics::Background::Col(if unlocked {
+                    Color::BLUE
                 } else {
-                    Color::RED
+                    rgba!(100, 100, 120, 1.0)
                 }),
             );
         }
 
-        let circle = Circle::new(
-            offset,
-            if progress.range_extended {
-                RANGE2
+        for d in dungeons.join() {
+            let mut colour = if d.completed {
+                Color::GREEN
+            } else if let Reward::Progress = d.reward {
+                Color::ORANGE
             } else {
-                RANGE1
-            },
-        );
+                Color::RED
+            };
+            // Faded out until the player can get to them.
+            if !in_range(d.position, &desert_map, &progress) {
+                colour.a = 0.35;
+            }
+            let rect = Rectangle::new(d.rect().pos + offset, d.rect().size);
+            self.window
+                .draw(&rect, quicksilver::graphics::Background::Col(colour));
+        }
+
+        for encounter in encounters.join().filter(|e| !e.resolved) {
+            let mut colour = encounter.kind.colour();
+            if !in_range(encounter.position, &desert_map, &progress) {
+                colour.a = 0.35;
+            }
+            let circle = Circle::new(encounter.position + offset, 6.0);
+            self.window
+                .draw(&circle, quicksilver::graphics::Background::Col(colour));
+        }
+
+        let circle = Circle::new(caravan.position + offset, 5.0);
         self.window.draw(
             &circle,
-            quicksilver::graphics::Background::Col(rgba!(0.0, 0.0, 250.0, 0.25)),
+            quicksilver::graphics::Background::Col(Color::YELLOW),
+        );
+
+        draw_text_centered(
+            &format!("Gold: {}", progress.currency),
+            Vector::new(700, 25),
+            self.font,
+            self.window,
+        );
+        draw_text_centered(
+            &format!("Water: {} / {}", caravan.water, caravan.max_water),
+            Vector::new(100, 25),
+            self.font,
+            self.window,
+        );
+        draw_text_centered(
+            &format!("Day {}", caravan.day),
+            Vector::new(100, 50),
+            self.font,
+            self.window,
         );
+        if let Some(text) = &announcement.text {
+            draw_text_centered(text, Vector::new(400, 80), self.font, self.window);
+        }
+
+        // Details of whatever is under the mouse, including how long it would take to get there.
+        let mouse_pos = input.raw_mouse_pos - offset;
+        let hovered = if let Some(oasis) = desert_map.oasis_at(mouse_pos) {
+            let name = if oasis.unlocked(&progress) {
+                "Oasis"
+            } else {
+                "Oasis (locked)"
+            };
+            Some((oasis.position, vec![name.to_string()]))
+        } else if let Some(d) = dungeons.join().find(|d| d.rect().contains(mouse_pos)) {
+            let mut lines = vec![
+                d.style.name().to_string(),
+                format!("Difficulty {}, {} floors", d.difficulty, d.floors),
+                format!("Reward: {}", d.reward.summary()),
+            ];
+            if d.scouted {
+                for option in d.reward.options() {
+                    match option {
+                        ChoiceOption::Item(item) => {
+                            lines.push(format!("  {}", item_registry.get(item).name))
+                        }
+                        ChoiceOption::Active(active) => lines.push(format!("  {}", active.name())),
+                        ChoiceOption::KeepActive => {}
+                    }
+                }
+            } else if !d.reward.options().is_empty() {
+                lines.push("  Get closer to scout it".to_string());
+            }
+            if d.completed {
+                lines.push("Cleared".to_string());
+            }
+            Some((d.position, lines))
+        } else {
+            encounters
+                .join()
+                .filter(|e| !e.resolved)
+                .find(|e| (e.position - mouse_pos).len2() < 10.0 * 10.0)
+                .map(|e| (e.position, vec![e.kind.name().to_string()]))
+        };
+        if let Some((destination, mut lines)) = hovered {
+            if !in_range(destination, &desert_map, &progress)
+                && desert_map.oasis_at(destination).is_none()
+            {
+                lines.push("Out of range".to_string());
+            } else {
+                lines.push(match caravan.travel_days(destination, &desert_map) {
+                    Some(days) => format!("{} days away", days),
+                    None => "No way through".to_string(),
+                });
+            }
+            draw_tooltip(&lines, input.raw_mouse_pos, self.font, self.window);
+        }
 
         draw_cursor(input.raw_mouse_pos, self.window);
     }
 }
 
+const TOOLTIP_WIDTH: f32 = 260.0;
+const TOOLTIP_LINE_HEIGHT: f32 = 22.0;
+
+// A box of text next to the mouse, kept on screen.
+fn draw_tooltip(lines: &[String], mouse_pos: Vector, font: &Font, window: &mut Window) {
+    let size = Vector::new(
+        TOOLTIP_WIDTH,
+        TOOLTIP_LINE_HEIGHT * lines.len() as f32 + 10.0,
+    );
+    let screen_size = window.screen_size();
+    let pos = Vector::new(
+        (mouse_pos.x + 15.0).min(screen_size.x - size.x),
+        (mouse_pos.y + 15.0).min(screen_size.y - size.y),
+    );
+    window.draw(
+        &Rectangle::new(pos, size),
+        quicksilver::graphics::Background::Col(rgba!(20, 16, 12, 0.9)),
+    );
+    for (i, line) in lines.iter().enumerate() {
+        draw_text_centered_with_size(
+            line,
+            pos + Vector::new(size.x / 2.0, 5.0 + TOOLTIP_LINE_HEIGHT * (i as f32 + 0.5)),
+            20.0,
+            font,
+            window,
+        );
+    }
+}
+
+const SHOP_ROW_SIZE: (f32, f32) = (420.0, 40.0);
+
+// Choices on the encounter screen, below the description.
+pub fn encounter_rows(count: usize) -> Vec<Rectangle> {
+    (0..count)
+        .map(|i| Rectangle::new(Vector::new(190.0, 300.0 + 50.0 * i as f32), SHOP_ROW_SIZE))
+        .collect()
+}
+
+pub struct RenderEncounter<'a> {
+    pub window: &'a mut Window,
+    pub font: &'a Font,
+}
+
+impl<'a: 'b, 'b> System<'b> for RenderEncounter<'a> {
+    type SystemData = (
+        Read<'b, CurrentEncounter>,
+        ReadStorage<'b, Encounter>,
+        Read<'b, ItemRegistry>,
+        Read<'b, Input>,
+    );
+
+    fn run(&mut self, (current_encounter, encounters, item_registry, input): Self::SystemData) {
+        let encounter = current_encounter
+            .entity
+            .and_then(|e| encounters.get(e))
+            .expect("The current encounter should be valid on the encounter screen.");
+        draw_text_centered(
+            encounter.kind.name(),
+            Vector::new(400, 80),
+            self.font,
+            self.window,
+        );
+        draw_text_centered_with_size(
+            &encounter.kind.description(&item_registry),
+            Vector::new(400, 180),
+            24.0,
+            self.font,
+            self.window,
+        );
+        let options = encounter.kind.options();
+        for (option, row) in options.iter().zip(encounter_rows(options.len())) {
+            let (name, description) = option.label(&item_registry);
+            draw_card(
+                &name,
+                &description,
+                encounter.kind.colour(),
+                row,
+                row.contains(input.raw_mouse_pos),
+                self.font,
+                self.window,
+            );
+        }
+    }
+}
+
+// Where each offer goes on the shop screen, shared with ShopSystem for hit testing.
+pub fn shop_rows(count: usize) -> Vec<Rectangle> {
+    (0..count)
+        .map(|i| Rectangle::new(Vector::new(150.0, 100.0 + 50.0 * i as f32), SHOP_ROW_SIZE))
+        .collect()
+}
+
+pub struct RenderShop<'a> {
+    pub window: &'a mut Window,
+    pub font: &'a Font,
+}
+
+impl<'a: 'b, 'b> System<'b> for RenderShop<'a> {
+    type SystemData = (
+        Read<'b, Shop>,
+        Entities<'b>,
+        ReadStorage<'b, Dungeon>,
+        Read<'b, PlayerProgression>,
+        Read<'b, ItemRegistry>,
+        Read<'b, Input>,
+        Read<'b, HealthConfig>,
+        Read<'b, DesertMap>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            shop,
+            entities,
+            dungeons,
+            progression,
+            item_registry,
+            input,
+            health_config,
+            desert_map,
+        ): Self::SystemData,
+    ) {
+        draw_text_centered("Oasis Market", Vector::new(400, 50), self.font, self.window);
+        draw_text_centered(
+            &format!("Gold: {}", progression.currency),
+            Vector::new(700, 25),
+            self.font,
+            self.window,
+        );
+        let offers = shop.offers((&entities, &dungeons).join(), &desert_map, &progression);
+        for (offer, row) in offers.iter().zip(shop_rows(offers.len())) {
+            let (name, description, colour) = match *offer {
+                ShopOffer::Rest => (
+                    "Rest".to_string(),
+                    format!("Rest at the oasis to heal {}.", health_config.rest_heal),
+                    rgba!(255, 105, 180, 1.0),
+                ),
+                ShopOffer::Item(item) => {
+                    let item = item_registry.get(item);
+                    (item.name.clone(), item.description.clone(), item.colour())
+                }
+                ShopOffer::Reroll(e) => {
+                    let names: Vec<_> = dungeons
+                        .get(e)
+                        .map(|d| d.reward.options())
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|option| match option {
+                            ChoiceOption::Item(item) => Some(item_registry.get(item).name.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    (
+                        format!("Reroll {}", names.join(" / ")),
+                        "Replace the items offered by this dungeon.".to_string(),
+                        Color::ORANGE,
+                    )
+                }
+            };
+            let hovered = row.contains(input.raw_mouse_pos);
+            draw_card(
+                &name,
+                &description,
+                colour,
+                row,
+                hovered,
+                self.font,
+                self.window,
+            );
+            let price = offer.price(&item_registry, &health_config, &progression);
+            let affordable = price <= progression.currency;
+            draw_text_centered_with_size(
+                &format!("{}g", price),
+                Vector::new(row.x() + row.width() + 50.0, row.center().y),
+                24.0,
+                self.font,
+                self.window,
+            );
+            if hovered {
+                let text = if affordable {
+                    description
+                } else {
+                    "You can't afford this.".to_string()
+                };
+                draw_text_centered_with_size(
+                    &text,
+                    Vector::new(400, 520),
+                    24.0,
+                    self.font,
+                    self.window,
+                );
+            }
+        }
+        draw_text_centered(
+            "Esc to leave",
+            Vector::new(400, 560),
+            self.font,
+            self.window,
+        );
+        draw_cursor(input.raw_mouse_pos, self.window);
+    }
+}
+
+const CARD_MAX_WIDTH: f32 = 300.0;
+const CARD_HEIGHT: f32 = 260.0;
+const CARD_GAP: f32 = 30.0;
+
+// Where the reward cards go on the choice screen, shared with ChoiceSystem for hit testing.
+pub fn choice_cards(screen_size: Vector, count: usize) -> Vec<Rectangle> {
+    let slot = screen_size.x / count as f32;
+    let width = (slot - CARD_GAP).min(CARD_MAX_WIDTH);
+    (0..count)
+        .map(|i| {
+            let centre = Vector::new(slot * (i as f32 + 0.5), screen_size.y / 2.0);
+            Rectangle::new(
+                centre - Vector::new(width, CARD_HEIGHT) / 2.0,
+                Vector::new(width, CARD_HEIGHT),
+            )
+        })
+        .collect()
+}
+
+// Big cards show the icon, name and description. Small ones (inventory rows) just the icon and name.
+fn draw_card(
+    name: &str,
+    description: &str,
+    colour: Color,
+    rect: Rectangle,
+    hovered: bool,
+    font: &Font,
+    window: &mut Window,
+) {
+    let border = Rectangle::new(rect.pos - Vector::new(3, 3), rect.size + Vector::new(6, 6));
+    window.draw(
+        &border,
+        quicksilver::graphics::Background::Col(if hovered {
+            Color::WHITE
+        } else {
+            rgba!(90, 90, 90, 1.0)
+        }),
+    );
+    window.draw(
+        &rect,
+        quicksilver::graphics::Background::Col(rgba!(40, 32, 24, 1.0)),
+    );
+    if rect.size.y >= 100.0 {
+        let icon = Circle::new(rect.pos + Vector::new(rect.size.x / 2.0, 60.0), 30.0);
+        window.draw(&icon, quicksilver::graphics::Background::Col(colour));
+        draw_text_centered_with_size(
+            name,
+            rect.pos + Vector::new(rect.size.x / 2.0, 130.0),
+            28.0,
+            font,
+            window,
+        );
+        draw_text_centered_with_size(
+            description,
+            rect.pos + Vector::new(rect.size.x / 2.0, 180.0),
+            18.0,
+            font,
+            window,
+        );
+    } else {
+        let icon = Circle::new(
+            rect.pos + Vector::new(rect.size.y / 2.0, rect.size.y / 2.0),
+            rect.size.y / 3.0,
+        );
+        window.draw(&icon, quicksilver::graphics::Background::Col(colour));
+        draw_text_centered_with_size(
+            name,
+            rect.pos + Vector::new((rect.size.x + rect.size.y) / 2.0, rect.size.y / 2.0),
+            20.0,
+            font,
+            window,
+        );
+    }
+}
+
 pub struct RenderChoice<'a> {
     pub window: &'a mut Window,
     pub font: &'a Font,
 }
 
 impl<'a: 'b, 'b> System<'b> for RenderChoice<'a> {
-    type SystemData = (Read<'b, CurrentDungeon>, ReadStorage<'b, Dungeon>);
+    type SystemData = (
+        Read<'b, CurrentDungeon>,
+        ReadStorage<'b, Dungeon>,
+        Read<'b, ItemRegistry>,
+        Read<'b, Input>,
+        ReadStorage<'b, PlayerControls>,
+    );
 
-    fn run(&mut self, (current_dungeon, dungeons): Self::SystemData) {
+    fn run(
+        &mut self,
+        (current_dungeon, dungeons, item_registry, input, players): Self::SystemData,
+    ) {
         let current_dungeon = current_dungeon
             .entity
             .expect("We should be playing a dungeon when we hit are doing choice.");
         let current_dungeon = dungeons
             .get(current_dungeon)
             .expect("The current dungeon should be valid when are doing choice.");
-        if let Reward::Choice(item1, item2) = current_dungeon.reward {
-            draw_text_centered(
-                "Choose Upgrade:",
-                Vector::new(400, 150),
-                &self.font,
-                self.window,
-            );
-            draw_text_centered(
-                &format!("{:?}", item1),
-                Vector::new(150, 300),
-                &self.font,
-                self.window,
-            );
-            draw_text_centered(
-                &format!("{:?}", item2),
-                Vector::new(650, 300),
-                &self.font,
+        let options = current_dungeon.reward.options();
+        if options.is_empty() {
+            panic!("Bad choice state");
+        }
+        let title = match current_dungeon.reward {
+            Reward::Cursed(_) => "A cursed item binds itself to you:",
+            Reward::ActiveSwap(_) => "Swap active item?",
+            _ => "Choose Upgrade:",
+        };
+        draw_text_centered(title, Vector::new(400, 100), &self.font, self.window);
+        let current_active = (&players).join().next().and_then(|p| p.active_item);
+        let cards = choice_cards(self.window.screen_size(), options.len());
+        for (option, card) in options.iter().zip(cards) {
+            let (name, description, colour) = match (*option, current_active) {
+                (ChoiceOption::Item(item), _) => {
+                    let item = item_registry.get(item);
+                    (item.name.clone(), item.description.clone(), item.colour())
+                }
+                (ChoiceOption::Active(active), _) => (
+                    active.name().to_string(),
+                    active.description().to_string(),
+                    active.colour(),
+                ),
+                (ChoiceOption::KeepActive, Some(active)) => (
+                    format!("Keep {}", active.name()),
+                    active.description().to_string(),
+                    active.colour(),
+                ),
+                (ChoiceOption::KeepActive, None) => (
+                    "Leave it".to_string(),
+                    "Carry on empty handed.".to_string(),
+                    Color::BLACK,
+                ),
+            };
+            draw_card(
+                &name,
+                &description,
+                colour,
+                card,
+                card.contains(input.raw_mouse_pos),
+                self.font,
                 self.window,
             );
-        } else {
-            panic!("Bad choice state");
         }
     }
 }
 
+const INVENTORY_ROW_SIZE: (f32, f32) = (220.0, 40.0);
+
 pub struct RenderInventory<'a> {
     pub window: &'a mut Window,
     pub font: &'a Font,
 }
 
 impl<'a: 'b, 'b> System<'b> for RenderInventory<'a> {
-    type SystemData = (ReadStorage<'b, PlayerControls>,);
+    type SystemData = (
+        ReadStorage<'b, PlayerControls>,
+        Read<'b, ItemRegistry>,
+        Read<'b, Input>,
+    );
 
-    fn run(&mut self, (players,): Self::SystemData) {
+    fn run(&mut self, (players, item_registry, input): Self::SystemData) {
         for p in (&players).join() {
             draw_text_centered(
                 "Inventory:",
@@ -296,12 +857,30 @@ impl<'a: 'b, 'b> System<'b> for RenderInventory<'a> {
                 self.window,
             );
             for (i, item) in p.items_acquired.iter().enumerate() {
-                draw_text_centered(
-                    &format!("{:?}", item),
-                    Vector::new(100.0, 100.0 + 50.0 * i as f32),
+                let item = item_registry.get(*item);
+                let row = Rectangle::new(
+                    Vector::new(10.0, 80.0 + 50.0 * i as f32),
+                    INVENTORY_ROW_SIZE,
+                );
+                let hovered = row.contains(input.raw_mouse_pos);
+                draw_card(
+                    &item.name,
+                    &item.description,
+                    item.colour(),
+                    row,
+                    hovered,
                     self.font,
                     self.window,
                 );
+                if hovered {
+                    draw_text_centered_with_size(
+                        &item.description,
+                        Vector::new(400, 550),
+                        24.0,
+                        self.font,
+                        self.window,
+                    );
+                }
             }
         }
     }
@@ -0,0 +1,179 @@
+use crate::gameplay::{apply_upgrade, Announcement, Combative, HealthConfig};
+use crate::items::{Item, ItemRegistry};
+use crate::physics::PhysicsComponent;
+use crate::player::PlayerControls;
+use crate::prelude::*;
+use crate::render::shop_rows;
+use crate::world_map::{self, DesertMap, Dungeon, Reward};
+use crate::{Input, PlayerProgression};
+
+const SHOP_ITEMS: usize = 3;
+const REROLL_PRICE: i32 = 15;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum ShopOffer {
+    // Heal a little, priced from the HealthConfig.
+    Rest,
+    Item(Item),
+    // Draw new items for this dungeon's item choice.
+    Reroll(Entity),
+}
+
+impl ShopOffer {
+    fn base_price(self, item_registry: &ItemRegistry, health_config: &HealthConfig) -> i32 {
+        match self {
+            ShopOffer::Rest => health_config.rest_price,
+            ShopOffer::Item(item) => item_registry.get(item).rarity.price(),
+            ShopOffer::Reroll(_) => REROLL_PRICE,
+        }
+    }
+
+    // Prices go up with every dungeon cleared so gold doesn't trivialise the late game.
+    pub fn price(
+        self,
+        item_registry: &ItemRegistry,
+        health_config: &HealthConfig,
+        progression: &PlayerProgression,
+    ) -> i32 {
+        let base = self.base_price(item_registry, health_config);
+        base + base * progression.dungeons_completed / 2
+    }
+}
+
+// The items for sale, restocked after each dungeon is cleared.
+#[derive(Default)]
+pub struct Shop {
+    items: Vec<Item>,
+    stocked_for: Option<i32>,
+}
+
+impl Shop {
+    fn restock_if_needed(&mut self, item_registry: &ItemRegistry, progression: &PlayerProgression) {
+        if self.stocked_for != Some(progression.dungeons_completed) {
+            self.items = item_registry.draw(false, SHOP_ITEMS, &mut rand::thread_rng());
+            self.stocked_for = Some(progression.dungeons_completed);
+        }
+    }
+
+    // Everything on offer, in display order. Rerolls are only offered for dungeons in range.
+    pub fn offers<'a>(
+        &self,
+        dungeons: impl Iterator<Item = (Entity, &'a Dungeon)>,
+        desert_map: &DesertMap,
+        progression: &PlayerProgression,
+    ) -> Vec<ShopOffer> {
+        let mut offers = vec![ShopOffer::Rest];
+        offers.extend(self.items.iter().map(|item| ShopOffer::Item(*item)));
+        for (e, d) in dungeons {
+            if d.completed || !world_map::in_range(d.position, desert_map, progression) {
+                continue;
+            }
+            if let Reward::Choice(..) | Reward::TripleChoice(..) = d.reward {
+                offers.push(ShopOffer::Reroll(e));
+            }
+        }
+        offers
+    }
+}
+
+fn reroll(reward: Reward, item_registry: &ItemRegistry) -> Reward {
+    let mut rng = rand::thread_rng();
+    match reward {
+        Reward::Choice(..) => {
+            let items = item_registry.draw(false, 2, &mut rng);
+            Reward::Choice(items[0], items[1])
+        }
+        Reward::TripleChoice(..) => {
+            let items = item_registry.draw(false, 3, &mut rng);
+            Reward::TripleChoice(items[0], items[1], items[2])
+        }
+        other => other,
+    }
+}
+
+pub struct ShopSystem;
+
+impl<'a> System<'a> for ShopSystem {
+    type SystemData = (
+        Read<'a, Input>,
+        Write<'a, Shop>,
+        Write<'a, PlayerProgression>,
+        Entities<'a>,
+        WriteStorage<'a, Dungeon>,
+        WriteStorage<'a, PlayerControls>,
+        WriteStorage<'a, Combative>,
+        WriteStorage<'a, PhysicsComponent>,
+        Read<'a, ItemRegistry>,
+        Write<'a, Announcement>,
+        Read<'a, HealthConfig>,
+        Read<'a, DesertMap>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            input,
+            mut shop,
+            mut progression,
+            entities,
+            mut dungeons,
+            mut players,
+            mut combatives,
+            mut physics,
+            item_registry,
+            mut announcement,
+            health_config,
+            desert_map,
+        ): Self::SystemData,
+    ) {
+        shop.restock_if_needed(&item_registry, &progression);
+        if !input.clicked {
+            return;
+        }
+        let offers = shop.offers((&entities, &dungeons).join(), &desert_map, &progression);
+        let rows = shop_rows(offers.len());
+        let chosen = rows
+            .iter()
+            .zip(offers.iter())
+            .find(|(row, _)| row.contains(input.raw_mouse_pos));
+        let offer = match chosen {
+            Some((_, offer)) => *offer,
+            None => return,
+        };
+        let price = offer.price(&item_registry, &health_config, &progression);
+        if progression.currency < price {
+            return;
+        }
+        match offer {
+            ShopOffer::Rest => {
+                let mut healed = false;
+                for (_, c) in (&players, &mut combatives).join() {
+                    healed = c.damage > 0;
+                    c.heal(health_config.rest_heal);
+                }
+                // Don't charge for healing someone who isn't hurt.
+                if !healed {
+                    return;
+                }
+            }
+            ShopOffer::Item(item) => {
+                apply_upgrade(
+                    item,
+                    &item_registry,
+                    &mut players,
+                    &mut combatives,
+                    &mut physics,
+                    &mut announcement,
+                );
+                shop.items.retain(|i| *i != item);
+            }
+            ShopOffer::Reroll(e) => {
+                let d = dungeons
+                    .get_mut(e)
+                    .expect("Dungeons offered for reroll should be valid.");
+                d.reward = reroll(d.reward, &item_registry);
+            }
+        }
+        progression.currency -= price;
+    }
+}
@@ -12,6 +12,11 @@ pub enum SoundRequest {
     BossShot,
     BossDeath,
     Pause,
+    Bomb,
+    Shield,
+    TimeSlow,
+    Turret,
+    Dodge,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -68,6 +73,26 @@ impl SoundSystem {
             SoundRequest::Pause,
             Asset::new(Sound::load("oryx_8-bit_sounds/interface/end_turn.wav")),
         );
+        sounds.insert(
+            SoundRequest::Bomb,
+            Asset::new(Sound::load("oryx_8-bit_sounds/impacts/boom_a.wav")),
+        );
+        sounds.insert(
+            SoundRequest::Shield,
+            Asset::new(Sound::load("oryx_8-bit_sounds/abilities/summon.wav")),
+        );
+        sounds.insert(
+            SoundRequest::TimeSlow,
+            Asset::new(Sound::load("oryx_8-bit_sounds/interface/end_turn.wav")),
+        );
+        sounds.insert(
+            SoundRequest::Turret,
+            Asset::new(Sound::load("oryx_8-bit_sounds/abilities/shoot_b.wav")),
+        );
+        sounds.insert(
+            SoundRequest::Dodge,
+            Asset::new(Sound::load("oryx_8-bit_sounds/abilities/summon.wav")),
+        );
         SoundSystem { sounds }
     }
 }
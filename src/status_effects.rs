@@ -0,0 +1,203 @@
+use crate::damage::{Damage, DamageType, Resistances};
+use crate::gameplay::Combative;
+use crate::prelude::*;
+use crate::{Event, EventQueue};
+use serde::Deserialize;
+
+const SLOW_MULTIPLIER: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum StatusKind {
+    Burn,
+    Poison,
+    Slow,
+    Stun,
+    Freeze,
+}
+
+impl StatusKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Burn => "Burning",
+            StatusKind::Poison => "Poisoned",
+            StatusKind::Slow => "Slowed",
+            StatusKind::Stun => "Stunned",
+            StatusKind::Freeze => "Frozen",
+        }
+    }
+
+    pub fn tint(self) -> Color {
+        match self {
+            StatusKind::Burn => rgba!(255, 90, 20, 1.0),
+            StatusKind::Poison => rgba!(80, 200, 60, 1.0),
+            StatusKind::Slow => rgba!(120, 100, 200, 1.0),
+            StatusKind::Stun => rgba!(255, 255, 120, 1.0),
+            StatusKind::Freeze => rgba!(150, 220, 255, 1.0),
+        }
+    }
+
+    fn duration(self) -> f32 {
+        match self {
+            StatusKind::Burn => 3.0,
+            StatusKind::Poison => 6.0,
+            StatusKind::Slow => 2.5,
+            StatusKind::Stun => 1.0,
+            StatusKind::Freeze => 2.0,
+        }
+    }
+
+    fn max_stacks(self) -> i32 {
+        match self {
+            StatusKind::Poison => 3,
+            _ => 1,
+        }
+    }
+
+    // How often the effect deals damage (one per stack), if it does at all.
+    fn tick_interval(self) -> Option<f32> {
+        match self {
+            StatusKind::Burn => Some(1.0),
+            StatusKind::Poison => Some(2.0),
+            _ => None,
+        }
+    }
+
+    fn damage_type(self) -> DamageType {
+        match self {
+            StatusKind::Burn => DamageType::Fire,
+            StatusKind::Poison => DamageType::Arcane,
+            _ => DamageType::Kinetic,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub stacks: i32,
+    pub expires: Timer,
+    next_tick: Timer,
+}
+
+#[derive(Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl Component for StatusEffects {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, kind: StatusKind, sim_time: SimTime) {
+        // Fire and ice cancel each other out.
+        match kind {
+            StatusKind::Burn => self.effects.retain(|e| e.kind != StatusKind::Freeze),
+            StatusKind::Freeze => self.effects.retain(|e| e.kind != StatusKind::Burn),
+            _ => {}
+        }
+        if let Some(effect) = self.effects.iter_mut().find(|e| e.kind == kind) {
+            match kind {
+                // Can't be reapplied until they wear off, otherwise things could be locked down forever.
+                StatusKind::Stun | StatusKind::Freeze => {}
+                _ => {
+                    effect.stacks = (effect.stacks + 1).min(kind.max_stacks());
+                    effect.expires.set(sim_time, kind.duration());
+                }
+            }
+            return;
+        }
+        self.effects.push(StatusEffect {
+            kind,
+            stacks: 1,
+            expires: Timer::new_set(sim_time, kind.duration()),
+            next_tick: Timer::new_set(sim_time, kind.tick_interval().unwrap_or(0.0)),
+        });
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    // Can't move, fire or dodge.
+    pub fn immobilised(&self) -> bool {
+        self.has(StatusKind::Stun) || self.has(StatusKind::Freeze)
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.has(StatusKind::Slow) {
+            SLOW_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+
+    // The most recently applied effect decides the colour.
+    pub fn tint(&self) -> Option<Color> {
+        self.effects.last().map(|e| e.kind.tint())
+    }
+}
+
+// Adds `kind` to the entity, giving it a StatusEffects component if it doesn't have one yet.
+pub fn apply_status(
+    entity: Entity,
+    kind: StatusKind,
+    statuses: &mut WriteStorage<StatusEffects>,
+    sim_time: SimTime,
+) {
+    if statuses.get(entity).is_none() {
+        statuses
+            .insert(entity, StatusEffects::default())
+            .expect("Status effects should only be applied to live entities.");
+    }
+    statuses.get_mut(entity).unwrap().apply(kind, sim_time);
+}
+
+pub struct StatusEffectSystem;
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, StatusEffects>,
+        WriteStorage<'a, Combative>,
+        Write<'a, EventQueue>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, Resistances>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut statuses, mut combatives, mut event_queue, sim_time, resistances): Self::SystemData,
+    ) {
+        for (entity, status, c, resistance) in (
+            &entities,
+            &mut statuses,
+            &mut combatives,
+            resistances.maybe(),
+        )
+            .join()
+        {
+            for effect in status.effects.iter_mut() {
+                let interval = match effect.kind.tick_interval() {
+                    Some(interval) => interval,
+                    None => continue,
+                };
+                if !effect.next_tick.expired(*sim_time) {
+                    continue;
+                }
+                effect.next_tick.set(*sim_time, interval);
+                // Shields and i-frames keep ticks off too, and the dead stay dead.
+                if c.invincibility_cooldown.expired(*sim_time) {
+                    let damage =
+                        Damage::of(effect.kind.damage_type(), effect.stacks).against(resistance);
+                    if c.take_damage(damage) {
+                        event_queue.enqueue(Event::EntityKilled(entity));
+                    }
+                }
+            }
+        }
+        for status in (&mut statuses).join() {
+            status.effects.retain(|e| !e.expires.expired(*sim_time));
+        }
+    }
+}
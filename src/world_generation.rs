@@ -1,81 +1,321 @@
-use crate::level_generation::LevelStyle;
+use crate::active_items::ActiveItem;
+use crate::campaign::{Campaign, Tier};
+use crate::encounters::{random_encounter, Encounter};
+use crate::items::ItemRegistry;
 use crate::prelude::*;
-use crate::world_map::{self, Dungeon, Reward, RANGE1, RANGE2};
+use crate::world_map::{DesertMap, Dungeon, Oasis, Reward, Terrain, MAP_CELL};
 
-const L1_DUNGEONS: usize = 2;
-const L2_DUNGEONS: usize = 2;
+// The desert fills the screen, in cells of MAP_CELL.
+const MAP_WIDTH: i32 = 40;
+const MAP_HEIGHT: i32 = 30;
+// Keeps sites away from the edge of the screen.
+const MAP_MARGIN: f32 = 30.0;
+// No two sites (oases or dungeons) are closer than this.
+const SITE_SPACING: f32 = 45.0;
+const POISSON_ATTEMPTS: usize = 30;
+// Spacing sites out on a map this size reliably leaves at least this many, which campaigns have to
+// fit their oases, dungeons and encounters into.
+pub const MAX_SITES: usize = 60;
+pub const ENCOUNTERS_PER_OASIS: usize = 2;
+const DUNE_FILL: f32 = 0.45;
+const ROCK_FILL: f32 = 0.35;
+const SMOOTHING_STEPS: usize = 4;
+// Give up on rocks if they keep cutting sites off.
+const TERRAIN_ATTEMPTS: usize = 20;
 
-pub fn generate_dungeons(world: &mut World) {
+// Item choices are the most common reward, and tougher dungeons are more likely to offer three.
+fn random_reward(registry: &ItemRegistry, difficulty: i32, rng: &mut impl Rng) -> Reward {
+    match rng.gen_range(0, 11) {
+        0..=3 => {
+            let items = registry.draw(false, 2, rng);
+            Reward::Choice(items[0], items[1])
+        }
+        4 | 5 if difficulty > 1 => {
+            let items = registry.draw(false, 3, rng);
+            Reward::TripleChoice(items[0], items[1], items[2])
+        }
+        4 => Reward::FullHeal,
+        5 | 6 => Reward::MaxHpShard,
+        7 => Reward::Cursed(registry.draw(true, 1, rng)[0]),
+        8 => {
+            let actives = ActiveItem::all();
+            Reward::ActiveSwap(actives[rng.gen_range(0, actives.len())])
+        }
+        _ => Reward::Currency(rng.gen_range(10, 20) * difficulty),
+    }
+}
+
+// Bridson's algorithm: points within `bounds` that are no closer than `spacing` to each other,
+// grown outwards from `start`.
+fn poisson_disc(bounds: Rectangle, spacing: f32, start: Vector, rng: &mut impl Rng) -> Vec<Vector> {
+    let mut points = vec![start];
+    let mut active = vec![start];
+    while !active.is_empty() {
+        let index = rng.gen_range(0, active.len());
+        let centre = active[index];
+        let mut found = None;
+        for _ in 0..POISSON_ATTEMPTS {
+            let candidate = centre
+                + Vector::from_angle(rng.gen_range(0.0, 360.0))
+                    .with_len(rng.gen_range(spacing, 2.0 * spacing));
+            if bounds.contains(candidate)
+                && points
+                    .iter()
+                    .all(|p| (*p - candidate).len2() >= spacing * spacing)
+            {
+                found = Some(candidate);
+                break;
+            }
+        }
+        match found {
+            Some(point) => {
+                points.push(point);
+                active.push(point);
+            }
+            None => {
+                active.swap_remove(index);
+            }
+        }
+    }
+    points
+}
+
+// A random site within range of `oasis`, if there are any left.
+fn take_site_in_range(
+    sites: &mut Vec<Vector>,
+    oasis: &Oasis,
+    rng: &mut impl Rng,
+) -> Option<Vector> {
+    let in_range: Vec<usize> = (0..sites.len())
+        .filter(|i| (sites[*i] - oasis.position).len() < oasis.range - MAP_CELL)
+        .collect();
+    if in_range.is_empty() {
+        None
+    } else {
+        Some(sites.swap_remove(in_range[rng.gen_range(0, in_range.len())]))
+    }
+}
+
+// Takes a random site within range of `oasis`, or the closest one if none are.
+fn take_site(sites: &mut Vec<Vector>, oasis: &Oasis, rng: &mut impl Rng) -> Vector {
+    if let Some(site) = take_site_in_range(sites, oasis, rng) {
+        return site;
+    }
+    let index = (0..sites.len())
+        .min_by(|a, b| {
+            let a = (sites[*a] - oasis.position).len2();
+            let b = (sites[*b] - oasis.position).len2();
+            a.partial_cmp(&b).unwrap()
+        })
+        .expect("Ran out of sites for dungeons.");
+    sites.swap_remove(index)
+}
+
+// Random fill smoothed with cellular automata, giving blobby patches.
+fn blobs(fill: f32, rng: &mut impl Rng) -> Vec<bool> {
+    let mut cells: Vec<bool> = (0..MAP_WIDTH * MAP_HEIGHT)
+        .map(|_| rng.gen_range(0.0, 1.0) < fill)
+        .collect();
+    for _ in 0..SMOOTHING_STEPS {
+        cells = (0..MAP_WIDTH * MAP_HEIGHT)
+            .map(|i| {
+                let (x, y) = (i % MAP_WIDTH, i / MAP_WIDTH);
+                let mut neighbours = 0;
+                for nx in x - 1..=x + 1 {
+                    for ny in y - 1..=y + 1 {
+                        if nx >= 0
+                            && ny >= 0
+                            && nx < MAP_WIDTH
+                            && ny < MAP_HEIGHT
+                            && cells[(nx + ny * MAP_WIDTH) as usize]
+                        {
+                            neighbours += 1;
+                        }
+                    }
+                }
+                neighbours >= 5
+            })
+            .collect();
+    }
+    cells
+}
+
+// Dunes and rocks, with the sites themselves kept clear and all reachable from the start.
+fn generate_terrain(map: &mut DesertMap, sites: &[Vector], rng: &mut impl Rng) {
+    let dunes = blobs(DUNE_FILL, rng);
+    for attempt in 0..=TERRAIN_ATTEMPTS {
+        let rocks = if attempt < TERRAIN_ATTEMPTS {
+            blobs(ROCK_FILL, rng)
+        } else {
+            vec![false; (MAP_WIDTH * MAP_HEIGHT) as usize]
+        };
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                let index = (x + y * MAP_WIDTH) as usize;
+                let terrain = if rocks[index] {
+                    Terrain::Rock
+                } else if dunes[index] {
+                    Terrain::Dunes
+                } else {
+                    Terrain::Sand
+                };
+                map.set(x, y, terrain);
+            }
+        }
+        for site in sites {
+            let (sx, sy) = map.cell(*site);
+            for x in sx - 1..=sx + 1 {
+                for y in sy - 1..=sy + 1 {
+                    map.set(x, y, Terrain::Sand);
+                }
+            }
+        }
+        if sites
+            .iter()
+            .all(|site| map.travel_distance(Vector::ZERO, *site).is_some())
+        {
+            return;
+        }
+    }
+}
+
+fn dungeon(
+    position: Vector,
+    reward: Reward,
+    tier: &Tier,
+    index: i32,
+    rng: &mut impl Rng,
+) -> Dungeon {
+    Dungeon {
+        position,
+        reward,
+        completed: false,
+        style: tier.random_style(rng),
+        difficulty: tier.difficulty,
+        floors: tier.floors,
+        current_floor: 1,
+        tier: index,
+        scouted: false,
+    }
+}
+
+// Picks where the next oasis of `tier` goes, preferring sites at the right distance that aren't too
+// close to other oases.
+fn take_oasis_site(
+    sites: &mut Vec<Vector>,
+    tier: &Tier,
+    oases: &[Oasis],
+    rng: &mut impl Rng,
+) -> Vector {
+    let (min, max) = tier.oasis_distance;
+    let candidates: Vec<usize> = (0..sites.len())
+        .filter(|i| {
+            let distance = sites[*i].len();
+            distance >= min
+                && distance <= max
+                && oases
+                    .iter()
+                    .all(|o| (o.position - sites[*i]).len() >= tier.range)
+        })
+        .collect();
+    let index = if candidates.is_empty() {
+        // Crowded map, settle for the right distance.
+        let middle = (min + max) / 2.0;
+        (0..sites.len())
+            .min_by(|a, b| {
+                let a = (sites[*a].len() - middle).abs();
+                let b = (sites[*b].len() - middle).abs();
+                a.partial_cmp(&b).unwrap()
+            })
+            .expect("Ran out of sites for oases.")
+    } else {
+        candidates[rng.gen_range(0, candidates.len())]
+    };
+    sites.swap_remove(index)
+}
+
+// Lays out the desert for the campaign: the starting oasis in the middle, then each tier's oases
+// with their dungeons around them. One dungeon per tier holds the boss that unlocks the next.
+// Encounters are scattered around every oasis.
+pub fn generate_desert(world: &mut World) {
     let mut rng = rand::thread_rng();
+    let half_size = Vector::new(
+        MAP_WIDTH as f32 * MAP_CELL / 2.0 - MAP_MARGIN,
+        MAP_HEIGHT as f32 * MAP_CELL / 2.0 - MAP_MARGIN,
+    );
+    let bounds = Rectangle::new(-half_size, half_size * 2.0);
+    let mut sites = poisson_disc(bounds, SITE_SPACING, Vector::ZERO, &mut rng);
+    sites.swap_remove(0);
+
+    let mut map = DesertMap::new(MAP_WIDTH, MAP_HEIGHT);
     let mut out = Vec::new();
-    let mut items = world_map::all_items();
-    for _ in 0..L1_DUNGEONS {
-        let index1 = rng.gen_range(0, items.len());
-        let item1 = items[index1];
-        items.remove(index1);
-        let index2 = rng.gen_range(0, items.len());
-        let item2 = items[index2];
-        items.remove(index2);
-        out.push(Dungeon {
-            position: Vector::from_angle(rng.gen_range(0.0, 360.0))
-                .with_len(rng.gen_range(50.0, RANGE1)),
-            reward: Reward::Choice(item1, item2),
-            completed: false,
-            style: if rng.gen_range(0.0, 1.0) > 0.5 {
-                LevelStyle::Cyclic
+    let campaign = world.read_resource::<Campaign>();
+    let registry = world.read_resource::<ItemRegistry>();
+    for (index, tier) in campaign.tiers.iter().enumerate() {
+        let index = index as i32;
+        let first_oasis = map.oases.len();
+        for _ in 0..tier.oases {
+            let position = if map.oases.is_empty() {
+                Vector::ZERO
             } else {
-                LevelStyle::CellularAutomata
-            },
-            difficulty: 1,
-        });
+                take_oasis_site(&mut sites, tier, &map.oases, &mut rng)
+            };
+            map.oases.push(Oasis {
+                position,
+                range: tier.range,
+                tier: index,
+            });
+        }
+        // The boss goes first so it gets a site the player can reach, starting from a random oasis.
+        let tier_oases = &map.oases[first_oasis..];
+        let start = rng.gen_range(0, tier_oases.len());
+        let position = (0..tier_oases.len())
+            .map(|i| &tier_oases[(start + i) % tier_oases.len()])
+            .filter_map(|oasis| take_site_in_range(&mut sites, oasis, &mut rng))
+            .next()
+            .unwrap_or_else(|| {
+                panic!(
+                    "No site within range of tier {}'s oases for its boss in {}",
+                    index, campaign.name
+                )
+            });
+        out.push(dungeon(position, Reward::Progress, tier, index, &mut rng));
+        for oasis in tier_oases.iter() {
+            for _ in 0..tier.dungeons {
+                let position = take_site(&mut sites, oasis, &mut rng);
+                let reward = random_reward(&registry, tier.difficulty, &mut rng);
+                out.push(dungeon(position, reward, tier, index, &mut rng));
+            }
+        }
     }
-    out.push(Dungeon {
-        position: Vector::from_angle(rng.gen_range(0.0, 360.0))
-            .with_len(rng.gen_range(50.0, RANGE1)),
-        reward: Reward::Progress,
-        completed: false,
-        style: if rng.gen_range(0.0, 1.0) > 0.5 {
-            LevelStyle::Cyclic
-        } else {
-            LevelStyle::CellularAutomata
-        },
-        difficulty: 1,
-    });
 
-    for _ in 0..L2_DUNGEONS {
-        let index1 = rng.gen_range(0, items.len());
-        let item1 = items[index1];
-        items.remove(index1);
-        let index2 = rng.gen_range(0, items.len());
-        let item2 = items[index2];
-        items.remove(index2);
-        out.push(Dungeon {
-            position: Vector::from_angle(rng.gen_range(0.0, 360.0))
-                .with_len(rng.gen_range(RANGE1, RANGE2)),
-            reward: Reward::Choice(item1, item2),
-            completed: false,
-            style: if rng.gen_range(0.0, 1.0) > 0.5 {
-                LevelStyle::Cyclic
-            } else {
-                LevelStyle::CellularAutomata
-            },
-            difficulty: 2,
-        });
+    let mut encounters = Vec::new();
+    for oasis in map.oases.iter() {
+        for _ in 0..ENCOUNTERS_PER_OASIS {
+            encounters.push(Encounter {
+                position: take_site(&mut sites, oasis, &mut rng),
+                kind: random_encounter(&registry, &mut rng),
+                resolved: false,
+            });
+        }
     }
-    out.push(Dungeon {
-        position: Vector::from_angle(rng.gen_range(0.0, 360.0))
-            .with_len(rng.gen_range(RANGE1, RANGE2)),
-        reward: Reward::Progress,
-        completed: false,
-        style: if rng.gen_range(0.0, 1.0) > 0.5 {
-            LevelStyle::Cyclic
-        } else {
-            LevelStyle::CellularAutomata
-        },
-        difficulty: 2,
-    });
 
+    let site_positions: Vec<Vector> = map
+        .oases
+        .iter()
+        .map(|o| o.position)
+        .chain(out.iter().map(|d| d.position))
+        .chain(encounters.iter().map(|e| e.position))
+        .collect();
+    generate_terrain(&mut map, &site_positions, &mut rng);
+
+    drop(registry);
+    drop(campaign);
     for d in out {
         world.create_entity().with(d).build();
     }
+    for e in encounters {
+        world.create_entity().with(e).build();
+    }
+    world.add_resource(map);
 }
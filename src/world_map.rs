@@ -1,49 +1,302 @@
-use crate::level_generation::{generate_level, EnemyType, LevelStyle};
+use crate::active_items::ActiveItem;
+use crate::encounters::{CurrentEncounter, Encounter};
+use crate::gameplay::{Announcement, Combative};
+use crate::items::Item;
+use crate::level_generation::{
+    generate_level, EnemyType, GeneratedLevel, GenerationMetrics, LevelStyle,
+};
 use crate::physics::TileMap;
 use crate::player::PlayerControls;
 use crate::prelude::*;
 use crate::{Camera, Input, PlayerProgression, ScreenSize, UIState, TILE_SIZE};
+use specs::storage::MaskedStorage;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Deref;
 
-pub const RANGE1: f32 = 150.0;
-pub const RANGE2: f32 = 300.0;
-
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub enum Item {
-    AttackSpeed,         // Done
-    MaxHealth,           // Done
-    TripleShot,          // Done
-    ExtraDamage,         // Done
-    Penetrating,         // Done
-    ReduceDodgeCooldown, // Done
-    SpeedIncrease,       // Needs work in player var
-    Backfire,            // Needs work in player probably a flag
-}
-
-pub fn all_items() -> Vec<Item> {
-    vec![
-        Item::AttackSpeed,
-        Item::MaxHealth,
-        Item::TripleShot,
-        Item::ExtraDamage,
-        Item::Penetrating,
-        Item::ReduceDodgeCooldown,
-        Item::SpeedIncrease,
-        Item::Backfire,
-    ]
-}
+// The desert is split into square cells of terrain, with the starting oasis in the middle.
+pub const MAP_CELL: f32 = 20.0;
+const DUNGEON_SIZE: f32 = 10.0;
+// Getting this close to a dungeon shows which items it offers.
+const SCOUT_RANGE: f32 = 60.0;
+// Map distance covered in a day, and how many days of water the caravan can carry.
+const DAY_DISTANCE: f32 = 50.0;
+const MAX_WATER: i32 = 12;
+// Each difficulty level above 1 makes enemies this much more likely to be the hard variant,
+// and to come with a second enemy.
+const HARD_ENEMY_CHANCE: f32 = 0.4;
+const EXTRA_ENEMY_CHANCE: f32 = 0.15;
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum Reward {
     Choice(Item, Item),
+    TripleChoice(Item, Item, Item),
+    FullHeal,
+    // Permanent +1 max health.
+    MaxHpShard,
+    // Has to be taken, good and bad.
+    Cursed(Item),
+    Currency(i32),
+    // Swap the active item for this one or keep the current one.
+    ActiveSwap(ActiveItem),
     Progress,
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum ChoiceOption {
+    Item(Item),
+    Active(ActiveItem),
+    KeepActive,
+}
+
+impl Reward {
+    // Cards shown on the choice screen for this reward, if any.
+    pub fn options(self) -> Vec<ChoiceOption> {
+        match self {
+            Reward::Choice(a, b) => vec![ChoiceOption::Item(a), ChoiceOption::Item(b)],
+            Reward::TripleChoice(a, b, c) => vec![
+                ChoiceOption::Item(a),
+                ChoiceOption::Item(b),
+                ChoiceOption::Item(c),
+            ],
+            Reward::Cursed(item) => vec![ChoiceOption::Item(item)],
+            Reward::ActiveSwap(active) => {
+                vec![ChoiceOption::Active(active), ChoiceOption::KeepActive]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn summary(self) -> &'static str {
+        match self {
+            Reward::Choice(..) => "Choice of two items",
+            Reward::TripleChoice(..) => "Choice of three items",
+            Reward::FullHeal => "Full heal",
+            Reward::MaxHpShard => "Max health shard",
+            Reward::Cursed(_) => "Cursed item",
+            Reward::Currency(_) => "Gold",
+            Reward::ActiveSwap(_) => "Active item",
+            Reward::Progress => "Boss",
+        }
+    }
+}
+
 pub struct Dungeon {
     pub position: Vector,
     pub reward: Reward,
     pub completed: bool,
     pub style: LevelStyle,
     pub difficulty: i32,
+    pub floors: i32,
+    pub current_floor: i32,
+    // The campaign tier it belongs to.
+    pub tier: i32,
+    // Whether the player has been close enough to see the reward's items.
+    pub scouted: bool,
+}
+
+impl Dungeon {
+    // Each floor down is a step harder than the one above it.
+    pub fn floor_difficulty(&self) -> i32 {
+        self.difficulty + self.current_floor - 1
+    }
+
+    pub fn on_final_floor(&self) -> bool {
+        self.current_floor >= self.floors
+    }
+
+    // Where it's drawn on the world map.
+    pub fn rect(&self) -> Rectangle {
+        Rectangle::new(self.position, Vector::new(DUNGEON_SIZE, DUNGEON_SIZE))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Terrain {
+    Sand,
+    // Slow going.
+    Dunes,
+    // Impassable.
+    Rock,
+}
+
+impl Terrain {
+    // Multiplier on the time taken to cross a cell, if it can be crossed at all.
+    fn travel_cost(self) -> Option<u32> {
+        match self {
+            Terrain::Sand => Some(1),
+            Terrain::Dunes => Some(2),
+            Terrain::Rock => None,
+        }
+    }
+}
+
+pub struct Oasis {
+    pub position: Vector,
+    // How far from it the player can venture once it's unlocked.
+    pub range: f32,
+    // Unlocked once the player has progressed this far.
+    pub tier: i32,
+}
+
+impl Oasis {
+    pub fn unlocked(&self, progression: &PlayerProgression) -> bool {
+        self.tier <= progression.tier
+    }
+}
+
+#[derive(Default)]
+pub struct DesertMap {
+    pub oases: Vec<Oasis>,
+    pub width: i32,
+    pub height: i32,
+    pub terrain: Vec<Terrain>,
+}
+
+impl DesertMap {
+    pub fn new(width: i32, height: i32) -> DesertMap {
+        DesertMap {
+            oases: Vec::new(),
+            width,
+            height,
+            terrain: vec![Terrain::Sand; (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Terrain {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            Terrain::Rock
+        } else {
+            self.terrain[(x + y * self.width) as usize]
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, terrain: Terrain) {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.terrain[(x + y * self.width) as usize] = terrain;
+        }
+    }
+
+    pub fn cell(&self, position: Vector) -> (i32, i32) {
+        (
+            (position.x / MAP_CELL + self.width as f32 / 2.0).floor() as i32,
+            (position.y / MAP_CELL + self.height as f32 / 2.0).floor() as i32,
+        )
+    }
+
+    // Top left corner of a cell.
+    pub fn cell_position(&self, x: i32, y: i32) -> Vector {
+        Vector::new(
+            (x as f32 - self.width as f32 / 2.0) * MAP_CELL,
+            (y as f32 - self.height as f32 / 2.0) * MAP_CELL,
+        )
+    }
+
+    pub fn oasis_at(&self, position: Vector) -> Option<&Oasis> {
+        self.oases
+            .iter()
+            .find(|o| (o.position - position).len2() < 10.0 * 10.0)
+    }
+
+    // Length of the quickest way from `from` to `to` around rocks, with dunes counting double.
+    // None if there's no way through.
+    pub fn travel_distance(&self, from: Vector, to: Vector) -> Option<f32> {
+        // Costs are in tenths of a cell so diagonals can stay integers.
+        let start = self.cell(from);
+        let goal = self.cell(to);
+        let mut best = vec![u32::max_value(); self.terrain.len()];
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, start)));
+        while let Some(Reverse((cost, (x, y)))) = queue.pop() {
+            if (x, y) == goal {
+                return Some(cost as f32 * MAP_CELL / 10.0);
+            }
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if (dx, dy) == (0, 0) {
+                        continue;
+                    }
+                    let (nx, ny) = (x + dx, y + dy);
+                    let multiplier = match self.get(nx, ny).travel_cost() {
+                        Some(multiplier) => multiplier,
+                        None => continue,
+                    };
+                    let step = if dx != 0 && dy != 0 { 14 } else { 10 };
+                    let next_cost = cost + step * multiplier;
+                    let index = (nx + ny * self.width) as usize;
+                    if next_cost < best[index] {
+                        best[index] = next_cost;
+                        queue.push(Reverse((next_cost, (nx, ny))));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// Whether the player can venture to `position`, i.e. it's near an unlocked oasis.
+pub fn in_range(position: Vector, map: &DesertMap, progression: &PlayerProgression) -> bool {
+    map.oases
+        .iter()
+        .any(|o| o.unlocked(progression) && (o.position - position).len2() <= o.range * o.range)
+}
+
+// Where the player is on the world map and how long they can keep travelling. The starting oasis
+// is at the origin.
+pub struct Caravan {
+    pub position: Vector,
+    pub water: i32,
+    pub max_water: i32,
+    pub day: i32,
+}
+
+impl Default for Caravan {
+    fn default() -> Self {
+        Caravan {
+            position: Vector::ZERO,
+            water: MAX_WATER,
+            max_water: MAX_WATER,
+            day: 1,
+        }
+    }
+}
+
+impl Caravan {
+    // None if the way is blocked.
+    pub fn travel_days(&self, destination: Vector, map: &DesertMap) -> Option<i32> {
+        map.travel_distance(self.position, destination)
+            .map(|distance| (distance / DAY_DISTANCE).ceil() as i32)
+    }
+
+    // Moves the caravan, refilling at oases. Returns how many days it went without water, or None
+    // if it couldn't get there.
+    pub fn travel(&mut self, destination: Vector, map: &DesertMap) -> Option<i32> {
+        let days = self.travel_days(destination, map)?;
+        let thirsty = (days - self.water).max(0);
+        self.water = (self.water - days).max(0);
+        self.day += days;
+        self.position = destination;
+        if map.oasis_at(destination).is_some() {
+            self.water = self.max_water;
+        }
+        Some(thirsty)
+    }
+}
+
+// The desert wears the player down (a hit point per day without water, storms) but won't finish
+// them off on its own.
+pub fn wear_down<D>(
+    amount: i32,
+    players: &Storage<PlayerControls, D>,
+    combatives: &mut WriteStorage<Combative>,
+) where
+    D: Deref<Target = MaskedStorage<PlayerControls>>,
+{
+    for (_, c) in (players, combatives).join() {
+        let survivable = (c.hp() - 1 + c.shield).max(0);
+        c.take_damage(amount.min(survivable));
+    }
 }
 
 impl Component for Dungeon {
@@ -55,11 +308,131 @@ pub struct CurrentDungeon {
     pub entity: Option<Entity>,
 }
 
+fn tile_centre(x: i32, y: i32) -> Vector {
+    Vector::new(
+        x as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+        y as f32 * TILE_SIZE + TILE_SIZE / 2.0,
+    )
+}
+
+// Creates the exit, enemies and pickups for a freshly generated floor, returning where the player
+// should start.
+pub fn spawn_floor(
+    level: &GeneratedLevel,
+    difficulty: i32,
+    entities: &Entities,
+    lazy_update: &LazyUpdate,
+    prefabs: &PrefabRegistry,
+) -> Vector {
+    prefabs
+        .spawn("exit", lazy_update.create_entity(entities))
+        .with(Transform {
+            position: Vector::new(
+                level.exit_position.0 as f32 * TILE_SIZE,
+                level.exit_position.1 as f32 * TILE_SIZE,
+            ),
+        })
+        .build();
+    let mut rng = rand::thread_rng();
+    let escalation = (difficulty - 1).max(0) as f32;
+    let hard_chance = (escalation * HARD_ENEMY_CHANCE).min(1.0);
+    let extra_chance = (escalation * EXTRA_ENEMY_CHANCE).min(1.0);
+    for cp in level.chode_positions.iter() {
+        let position = tile_centre(cp.0, cp.1);
+        // Extra enemies share the tile with the generated one, side by side.
+        let offsets: &[f32] = if rng.gen::<f32>() < extra_chance {
+            &[-TILE_SIZE / 4.0, TILE_SIZE / 4.0]
+        } else {
+            &[0.0]
+        };
+        for &offset in offsets {
+            let name = match (rng.gen::<f32>() < hard_chance, cp.2) {
+                (false, EnemyType::Chode) => "chode",
+                (false, EnemyType::Shotgunner) => "shotgunner",
+                (false, EnemyType::Spinner) => "spinner",
+                (true, EnemyType::Chode) => "hard_chode",
+                (true, EnemyType::Shotgunner) => "hard_shotgunner",
+                (true, EnemyType::Spinner) => "hard_spinner",
+            };
+            prefabs
+                .spawn(name, lazy_update.create_entity(entities))
+                .with(Transform {
+                    position: position + Vector::new(offset, 0.0),
+                })
+                .build();
+        }
+    }
+    for pp in level.pickup_positions.iter() {
+        prefabs
+            .spawn("heart", lazy_update.create_entity(entities))
+            .with(Transform {
+                position: tile_centre(pp.0, pp.1),
+            })
+            .build();
+    }
+    tile_centre(level.start_position.0, level.start_position.1)
+}
+
+// Puts the player at `position` and snaps the camera onto them.
+pub fn move_player<D>(
+    position: Vector,
+    entities: &Entities,
+    players: &Storage<PlayerControls, D>,
+    transforms: &mut WriteStorage<Transform>,
+    cameras: &mut WriteStorage<Camera>,
+    screen_size: &ScreenSize,
+) where
+    D: Deref<Target = MaskedStorage<PlayerControls>>,
+{
+    for (_, player_transform) in (players, &mut *transforms).join() {
+        player_transform.position = position;
+    }
+    for (camera, transform) in (cameras, transforms).join() {
+        for (ent, _) in (entities, players).join() {
+            camera.follow = ent;
+        }
+        transform.position = position - screen_size.size / 2.0;
+    }
+}
+
+// Starts `dungeon` from its first floor.
+#[allow(clippy::too_many_arguments)]
+pub fn enter_dungeon<D>(
+    entity: Entity,
+    dungeon: &mut Dungeon,
+    generation_metrics: &mut GenerationMetrics,
+    tile_map: &mut TileMap,
+    current_dungeon: &mut CurrentDungeon,
+    entities: &Entities,
+    lazy_update: &LazyUpdate,
+    prefabs: &PrefabRegistry,
+    players: &Storage<PlayerControls, D>,
+    transforms: &mut WriteStorage<Transform>,
+    cameras: &mut WriteStorage<Camera>,
+    screen_size: &ScreenSize,
+) where
+    D: Deref<Target = MaskedStorage<PlayerControls>>,
+{
+    dungeon.current_floor = 1;
+    dungeon.scouted = true;
+    let level = generate_level(dungeon.style, generation_metrics);
+    let start = spawn_floor(
+        &level,
+        dungeon.floor_difficulty(),
+        entities,
+        lazy_update,
+        prefabs,
+    );
+    *tile_map = level.tile_map;
+    current_dungeon.entity = Some(entity);
+    move_player(start, entities, players, transforms, cameras, screen_size);
+}
+
 pub struct WorldMapScreen;
 
 impl<'a> System<'a> for WorldMapScreen {
     type SystemData = (
-        ReadStorage<'a, Dungeon>,
+        WriteStorage<'a, Dungeon>,
         Write<'a, UIState>,
         Read<'a, ScreenSize>,
         Read<'a, Input>,
@@ -67,16 +440,24 @@ impl<'a> System<'a> for WorldMapScreen {
         ReadStorage<'a, PlayerControls>,
         WriteStorage<'a, Transform>,
         Read<'a, LazyUpdate>,
+        Read<'a, PrefabRegistry>,
         Entities<'a>,
         Write<'a, CurrentDungeon>,
         Read<'a, PlayerProgression>,
         WriteStorage<'a, Camera>,
+        Write<'a, GenerationMetrics>,
+        Write<'a, Announcement>,
+        Write<'a, Caravan>,
+        WriteStorage<'a, Combative>,
+        Read<'a, DesertMap>,
+        ReadStorage<'a, Encounter>,
+        Write<'a, CurrentEncounter>,
     );
 
     fn run(
         &mut self,
         (
-            dungeons,
+            mut dungeons,
             mut ui_state,
             screen_size,
             input,
@@ -84,69 +465,81 @@ impl<'a> System<'a> for WorldMapScreen {
             players,
             mut transforms,
             lazy_update,
+            prefabs,
             entities,
             mut current_dungeon,
             progression,
             mut cameras,
+            mut generation_metrics,
+            mut announcement,
+            mut caravan,
+            mut combatives,
+            desert_map,
+            encounters,
+            mut current_encounter,
         ): Self::SystemData,
     ) {
+        for d in (&mut dungeons).join() {
+            if (d.position - caravan.position).len2() <= SCOUT_RANGE * SCOUT_RANGE {
+                d.scouted = true;
+            }
+        }
         let offset = screen_size.size / 2.0;
         let mouse_pos = input.raw_mouse_pos - offset;
-        for (e, d) in (&entities, &dungeons).join() {
+        // Unlocked oases can always be reached, even if it's a thirsty trip.
+        if let Some(oasis) = desert_map.oasis_at(mouse_pos) {
+            if input.clicked && oasis.unlocked(&progression) {
+                if let Some(thirsty) = caravan.travel(oasis.position, &desert_map) {
+                    wear_down(thirsty, &players, &mut combatives);
+                    *ui_state = UIState::Shop;
+                }
+            }
+            return;
+        }
+        for (e, encounter) in (&entities, &encounters).join() {
             if input.fire
-                && (d.position - mouse_pos).len2() < 10.0 * 10.0
-                && (d.position.len2() <= RANGE1 * RANGE1 || progression.range_extended)
+                && (encounter.position - mouse_pos).len2() < 10.0 * 10.0
+                && in_range(encounter.position, &desert_map, &progression)
+                && !encounter.resolved
+            {
+                let thirsty = match caravan.travel(encounter.position, &desert_map) {
+                    Some(thirsty) => thirsty,
+                    None => continue,
+                };
+                wear_down(thirsty, &players, &mut combatives);
+                announcement.text = None;
+                current_encounter.entity = Some(e);
+                *ui_state = UIState::Encounter;
+                return;
+            }
+        }
+        for (e, d) in (&entities, &mut dungeons).join() {
+            if input.fire
+                && d.rect().contains(mouse_pos)
+                && in_range(d.position, &desert_map, &progression)
                 && !d.completed
             {
+                let thirsty = match caravan.travel(d.position, &desert_map) {
+                    Some(thirsty) => thirsty,
+                    None => continue,
+                };
+                wear_down(thirsty, &players, &mut combatives);
                 *ui_state = UIState::Playing;
-                let level = generate_level(d.style);
-                *tile_map = level.tile_map;
-                let mut player_start_position = Vector::new(-1.0, -1.0);
-                for (_, player_movement) in (&players, &mut transforms).join() {
-                    player_movement.position = Vector::from(level.start_position) * TILE_SIZE
-                        + Vector::new(TILE_SIZE / 2.0, TILE_SIZE / 2.0);
-                    player_start_position = player_movement.position;
-                }
-                lazy_update
-                    .create_entity(&entities)
-                    .with_exit_prefab()
-                    .with(Transform {
-                        position: Vector::new(
-                            level.exit_position.0 as f32 * TILE_SIZE,
-                            level.exit_position.1 as f32 * TILE_SIZE,
-                        ),
-                    })
-                    .build();
-                for cp in level.chode_positions {
-                    let mut ent = lazy_update.create_entity(&entities);
-                    if d.difficulty == 1 {
-                        match cp.2 {
-                            EnemyType::Chode => ent = ent.with_chode_prefab(),
-                            EnemyType::Shotgunner => ent = ent.with_shotgunner_prefab(),
-                            EnemyType::Spinner => ent = ent.with_spinner_prefab(),
-                        }
-                    } else {
-                        match cp.2 {
-                            EnemyType::Chode => ent = ent.with_hard_chode_prefab(),
-                            EnemyType::Shotgunner => ent = ent.with_hard_shotgunner_prefab(),
-                            EnemyType::Spinner => ent = ent.with_hard_spinner_prefab(),
-                        }
-                    }
-                    ent.with(Transform {
-                        position: Vector::new(
-                            cp.0 as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                            cp.1 as f32 * TILE_SIZE + TILE_SIZE / 2.0,
-                        ),
-                    })
-                    .build();
-                }
-                current_dungeon.entity = Some(e);
-                for (camera, transform) in (&mut cameras, &mut transforms).join() {
-                    for (ent, _) in (&entities, &players).join() {
-                        camera.follow = ent;
-                    }
-                    transform.position = player_start_position - screen_size.size / 2.0;
-                }
+                announcement.text = None;
+                enter_dungeon(
+                    e,
+                    d,
+                    &mut generation_metrics,
+                    &mut tile_map,
+                    &mut current_dungeon,
+                    &entities,
+                    &lazy_update,
+                    &prefabs,
+                    &players,
+                    &mut transforms,
+                    &mut cameras,
+                    &screen_size,
+                );
             }
         }
     }